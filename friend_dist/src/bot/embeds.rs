@@ -0,0 +1,160 @@
+//! Embeds the bot posts: account listings, run history and the live queue progress.
+
+use chrono::{DateTime, Utc};
+use serenity::all::{CreateEmbed, CreateEmbedFooter, Timestamp};
+
+use crate::db::{Account, RunRecord};
+use crate::queue::{QueueKind, QueueSnapshot};
+use crate::retry;
+
+/// Discord allows 4096 characters in a description; leave room for the marker.
+const MAX_DESCRIPTION: usize = 4000;
+
+fn status_emoji(status: &str) -> &'static str {
+    if status == "done" { "✅" }
+    else if status.starts_with("error") { "❌" }
+    else if status == "pending" { "⏳" }
+    else { "💤" }
+}
+
+/// Cuts `text` to the description limit on a character boundary.
+fn truncate_description(text: &mut String) {
+    if text.len() > MAX_DESCRIPTION {
+        let mut cut = MAX_DESCRIPTION;
+        while !text.is_char_boundary(cut) { cut -= 1; }
+        text.truncate(cut);
+        text.push_str("\n... (truncated)");
+    }
+}
+
+/// One entry per account with status and last run. `with_server` adds the target server.
+pub fn account_list(title: String, color: u32, accounts: &[Account], with_server: bool) -> CreateEmbed {
+    let mut description = String::new();
+    for acc in accounts {
+        let last_run_str = match &acc.last_run {
+            Some(lr) => match DateTime::parse_from_rfc3339(lr) {
+                Ok(parsed) => format!("<t:{}:R>", parsed.timestamp()),
+                Err(_) => "Invalid Date".to_string(),
+            },
+            None => "Never".to_string(),
+        };
+        let name = if with_server {
+            format!("**{}** ({})", acc.name, acc.target_server.as_deref().unwrap_or("Default"))
+        } else {
+            format!("**{}**", acc.name)
+        };
+        description.push_str(&format!("{}\n{} {} • 🕒 {}\n\n", name, status_emoji(&acc.status), acc.status, last_run_str));
+    }
+    truncate_description(&mut description);
+
+    CreateEmbed::new()
+        .title(title)
+        .color(color)
+        .description(description)
+        .timestamp(Timestamp::now())
+}
+
+/// The handout-enabled accounts and their daily status.
+pub fn handout_list(accounts: &[Account]) -> CreateEmbed {
+    let mut description = String::new();
+    for acc in accounts {
+        description.push_str(&format!("**{}** • {} {}\n", acc.name, status_emoji(&acc.status), acc.status));
+    }
+    truncate_description(&mut description);
+
+    CreateEmbed::new()
+        .title("🎁 Handout List")
+        .color(0xffa500) // Orange
+        .description(description)
+        .timestamp(Timestamp::now())
+}
+
+/// `runs` of one account over the last `days`, newest first.
+pub fn run_history(name: &str, days: u32, runs: &[RunRecord]) -> CreateEmbed {
+    let mut description = String::new();
+    for (i, run) in runs.iter().enumerate() {
+        let outcome_emoji = if run.outcome == "completed" { "✅" } else { "❌" };
+        let started = DateTime::parse_from_rfc3339(&run.started_at).ok();
+        let ended = DateTime::parse_from_rfc3339(&run.ended_at).ok();
+        let when = started.map(|t| format!("<t:{}:f>", t.timestamp())).unwrap_or_else(|| "Invalid Date".to_string());
+        let duration = match (started, ended) {
+            (Some(s), Some(e)) => format!("{}s", (e - s).num_seconds()),
+            _ => "?".to_string(),
+        };
+
+        description.push_str(&format!(
+            "#{} {} • {} {} • {} • ⏱️ {} • retries {}{}\n",
+            i + 1,
+            when,
+            outcome_emoji,
+            run.outcome,
+            run.mode,
+            duration,
+            run.retries,
+            if run.transcript.is_some() { " • 📎" } else { "" }
+        ));
+        if let Some(prompt) = &run.final_prompt {
+            description.push_str(&format!("> {}\n", prompt.chars().take(100).collect::<String>()));
+        }
+    }
+    truncate_description(&mut description);
+
+    CreateEmbed::new()
+        .title(format!("📜 Run history for {}", name))
+        .description(description)
+        .footer(CreateEmbedFooter::new(format!("Last {} days • {} runs", days, runs.len())))
+        .color(0x9b59b6)
+        .timestamp(Timestamp::now())
+}
+
+/// Progress embed for a queue: live sessions, what's left, what's backing off and an ETA.
+/// `started` is the manager's start time (None for a /queue_status snapshot).
+pub fn queue_status(status: &QueueSnapshot, kind: &QueueKind, started: Option<DateTime<Utc>>, finished: bool) -> CreateEmbed {
+    let now = Utc::now();
+    let mut running = String::new();
+    for (name, progress) in &status.sessions {
+        let state = progress.state.map_or("Connecting".to_string(), |s| format!("{:?}", s));
+        let mode = progress.mode.map_or("", |m| m.as_str());
+        running.push_str(&format!("**{}** {} • {} • {}\n", name, mode, state, retry::describe((now - progress.started).to_std().unwrap_or_default())));
+        if let Some(prompt) = &progress.last_prompt {
+            running.push_str(&format!("> {}\n", prompt.chars().take(80).collect::<String>()));
+        }
+    }
+    if running.is_empty() {
+        running = "Nothing running".to_string();
+    }
+    if running.len() > 1000 {
+        running = running.chars().take(1000).collect();
+    }
+
+    let mut delayed_text = status.delayed.iter().take(10)
+        .map(|(name, at)| format!("{} • <t:{}:R>", name, at.timestamp()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    if status.delayed.len() > 10 {
+        delayed_text.push_str(&format!("\n... and {} more", status.delayed.len() - 10));
+    }
+
+    let (title, color) = match (finished, kind) {
+        (true, _) => (format!("✅ {}: finished", kind.label()), 0x2ecc71),
+        (false, QueueKind::Handout) => (format!("⚙️ {}: handout run", kind.label()), 0x3498db),
+        (false, QueueKind::Daily(_)) => (format!("⚙️ {}: daily run", kind.label()), 0x3498db),
+    };
+    let mut embed = CreateEmbed::new()
+        .title(title)
+        .color(color)
+        .field(format!("Running ({}/{})", status.sessions.len(), status.concurrency), running, false)
+        .field("Remaining", status.remaining.to_string(), true)
+        .field("Delayed retry", status.delayed.len().to_string(), true)
+        .timestamp(Timestamp::now());
+    if !finished && status.busy() {
+        embed = embed.field("ETA", format!("<t:{}:R>", status.eta.timestamp()), true);
+    }
+    if !delayed_text.is_empty() {
+        embed = embed.field("Backing off", delayed_text, false);
+    }
+    if let Some(started) = started {
+        embed = embed.footer(CreateEmbedFooter::new(format!("Started {} UTC", started.format("%H:%M:%S"))));
+    }
+    embed
+}
@@ -0,0 +1,47 @@
+//! The Discord side of the bot: queue managers and workers (`runner`), the embeds they
+//! post (`embeds`) and the log channel and admin alerts everything reports to. The binary
+//! only registers slash commands and hands them to these.
+
+use std::sync::Arc;
+
+use serenity::all::{ChannelId, CreateMessage, Http, UserId};
+use tokio::sync::Mutex;
+
+use crate::db::Database;
+
+pub mod embeds;
+pub mod runner;
+
+/// Posts `message` to the log channel, unless bot messages are muted or the log channel is
+/// `skip_channel` (where the message was already sent).
+pub async fn log_message(db: Arc<Mutex<Database>>, http: Arc<Http>, message: String, skip_channel: Option<ChannelId>) {
+    let db = db.lock().await;
+    if let Some(true) = db.data.settings.mute_bot_messages {
+        return;
+    }
+    if let Some(channel_id_str) = &db.data.settings.log_channel_id {
+        if let Ok(channel_id) = channel_id_str.parse::<u64>() {
+            let channel = ChannelId::new(channel_id);
+            if Some(channel) == skip_channel {
+                return;
+            }
+            let _ = channel.say(&http, message).await;
+        }
+    }
+}
+
+/// Posts `message` to the log channel and DMs it to the owner and every admin.
+pub async fn alert_admins(db: &Arc<Mutex<Database>>, http: &Arc<Http>, message: String) {
+    let mut recipients = db.lock().await.get_admins();
+    if let Ok(owner_id) = std::env::var("OWNER_ID") {
+        if !recipients.contains(&owner_id) {
+            recipients.insert(0, owner_id);
+        }
+    }
+    for id in recipients.iter().filter_map(|id| id.parse::<u64>().ok()) {
+        if let Err(e) = UserId::new(id).direct_message(http, CreateMessage::new().content(&message)).await {
+            println!("[WARN] Could not DM admin {}: {}", id, e);
+        }
+    }
+    log_message(Arc::clone(db), Arc::clone(http), message, None).await;
+}
@@ -0,0 +1,579 @@
+//! Queue managers, the workers they spawn and the scheduler loop. Managers claim accounts
+//! from the shared `WorkerPool`, one worker plays each claimed account, and results are
+//! reported to Discord as they come in.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serenity::all::{ChannelId, CreateMessage, EditMessage, Http};
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+use super::{alert_admins, embeds, log_message};
+use crate::crypto::CryptoError;
+use crate::db::{self, Account, Database, RunRecord};
+use crate::pool::{ClaimError, WorkerPool};
+use crate::protocol::config::ClientConfig;
+use crate::protocol::socket::{do_http_refresh, CookieCheck, EvertextClient, RunMode, SessionErrorKind, SessionOutcome, SessionResult};
+use crate::queue::{until, QueueKind, QueueSnapshot, WorkerJob, WorkerSignal};
+use crate::retry::{self, RetryClass};
+use crate::scheduler::{self, Job};
+
+/// How often a queue manager's progress embed is refreshed while sessions run.
+const STATUS_REFRESH_SECS: u64 = 15;
+
+/// Probes the pool cookies (or just the one labelled `only`) and records the verdicts:
+/// refused cookies are quarantined and quarantined ones that pass are restored.
+/// Admins are alerted about every cookie that failed. Returns one line per cookie.
+pub async fn check_cookies(db_clone: &Arc<Mutex<Database>>, http_clone: &Arc<Http>, only: Option<&str>) -> Vec<String> {
+    let (cookies, config) = {
+        let db = db_clone.lock().await;
+        let cookies: Vec<_> = db.data.settings.session_cookies.iter()
+            .filter(|c| only.is_none_or(|label| c.label == label))
+            .filter_map(|c| Some((c.label.clone(), db.cookie_secret(c).ok()?)))
+            .collect();
+        (cookies, ClientConfig::resolve(db.data.settings.client.as_ref()))
+    };
+
+    let mut lines = Vec::new();
+    let mut failures = Vec::new();
+    for (label, cookie) in cookies {
+        let (verdict, rotated) = EvertextClient::probe(&config, &cookie).await;
+        keep_rotated_cookie(db_clone, &label, rotated.as_deref()).await;
+        let mut db = db_clone.lock().await;
+        let line = match &verdict {
+            CookieCheck::Valid => match db.cookie_validated(&label) {
+                Ok(true) => format!("✅ `{}` is valid again and was restored from quarantine", label),
+                _ => format!("✅ `{}` is valid", label),
+            },
+            CookieCheck::LoginRequired => {
+                let _ = db.quarantine_cookie(&label);
+                format!("🚫 `{}` has expired (login required) and is quarantined", label)
+            },
+            CookieCheck::Inconclusive(reason) => {
+                let _ = db.cookie_check_failed(&label);
+                format!("⚠️ `{}` could not be checked: {}", label, reason)
+            },
+        };
+        println!("[INFO] Cookie check: {}", line);
+        if verdict != CookieCheck::Valid {
+            failures.push(line.clone());
+        }
+        lines.push(line);
+    }
+
+    if !failures.is_empty() {
+        let healthy = db_clone.lock().await.healthy_cookies();
+        let message = format!(
+            "⚠️ **Session cookie check failed**\n{}\n{} healthy cookie(s) left. Replace expired cookies with /set_cookies.",
+            failures.join("\n"), healthy
+        );
+        alert_admins(db_clone, http_clone, message).await;
+    }
+    lines
+}
+
+/// Persists the cookie the server rotated `label` to during a refresh, if it did.
+async fn keep_rotated_cookie(db_clone: &Arc<Mutex<Database>>, label: &str, rotated: Option<&str>) {
+    let Some(value) = rotated else { return };
+    match db_clone.lock().await.rotate_cookie(label, value) {
+        Ok(true) => println!("[INFO] Session cookie '{}' was rotated by the server and saved.", label),
+        Ok(false) => {},
+        Err(e) => println!("[ERROR] Failed to save rotated session cookie '{}': {}", label, e),
+    }
+}
+
+/// Runs a queue manager in the background. The manager claims accounts from the
+/// shared worker pool (up to `Settings.maxConcurrentSessions`) and spawns one
+/// worker per claimed account until nothing eligible is left.
+pub fn spawn_manager(db_clone: Arc<Mutex<Database>>, pool: Arc<WorkerPool>, http_clone: Arc<Http>, kind: QueueKind, source_channel: Option<ChannelId>) {
+    tokio::spawn(async move {
+        let key = kind.key();
+        let Some(stop) = pool.start_manager(&key) else {
+            if let Some(chan) = source_channel {
+                let _ = chan.say(&http_clone, format!("[WARN] {}: Already in progress.", kind.label())).await;
+            }
+            return;
+        };
+
+        let mut workers = tokio::task::JoinSet::new();
+        // Sessions started per account during this manager's lifetime
+        let mut attempts: HashMap<String, u32> = HashMap::new();
+        let mut halted = false;
+
+        // Progress is shown in one embed that is edited in place; per-account
+        // results then only go to the log channel instead of the source channel.
+        let started = Utc::now();
+        let mut status_msg = None;
+        if let Some(chan) = source_channel {
+            let embed = {
+                let db = db_clone.lock().await;
+                embeds::queue_status(&QueueSnapshot::collect(&db, &pool, &kind, &attempts, Utc::now()), &kind, Some(started), false)
+            };
+            status_msg = chan.send_message(&http_clone, CreateMessage::new().embed(embed)).await.ok();
+        }
+        let worker_channel = if status_msg.is_some() { None } else { source_channel };
+        let mut refresh = tokio::time::interval(tokio::time::Duration::from_secs(STATUS_REFRESH_SECS));
+
+        loop {
+            if !halted && stop.is_cancelled() {
+                halted = true;
+            }
+
+            // Earliest time a backing-off account becomes eligible again
+            let mut wake_at = None;
+            if !halted {
+                let now = Utc::now();
+                let (candidates, next_due, limit, config) = {
+                    let db = db_clone.lock().await;
+                    (kind.candidates(&db, &attempts, now), kind.next_retry(&db, now), db.concurrency(), ClientConfig::resolve(db.data.settings.client.as_ref()))
+                };
+                wake_at = next_due;
+
+                for acc in &candidates {
+                    match pool.try_claim(&acc.name, kind.mode(), limit) {
+                        Ok(lease) => {
+                            let picked = db_clone.lock().await.pick_cookie();
+                            let Some((cookie_label, cookie)) = picked else {
+                                if let Some(chan) = source_channel {
+                                    let _ = chan.say(&http_clone, format!("[ERROR] {}: No healthy session cookies. Use /set_cookies.", kind.label())).await;
+                                }
+                                halted = true;
+                                break;
+                            };
+                            let count = attempts.entry(acc.name.clone()).or_insert(0);
+                            let job = WorkerJob { acc: acc.clone(), mode: kind.mode(), cookie_label, cookie, config: config.clone(), retries: *count, lease };
+                            *count += 1;
+                            workers.spawn(run_account(Arc::clone(&db_clone), Arc::clone(&http_clone), worker_channel, job));
+                        },
+                        Err(ClaimError::AlreadyRunning) => continue,
+                        Err(ClaimError::PoolFull | ClaimError::Reserved) => break,
+                    }
+                }
+
+                if workers.is_empty() {
+                    if halted || (candidates.is_empty() && next_due.is_none()) {
+                        break;
+                    }
+                    if candidates.is_empty() {
+                        // Only backing-off accounts left; nap until the first one is due
+                        // (at most 30s, so /force_stop_all is still noticed).
+                        tokio::time::sleep(until(next_due, 30)).await;
+                    } else {
+                        // Everything left is held by another manager; wait for it to free up.
+                        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+                    }
+                    continue;
+                }
+            } else if workers.is_empty() {
+                break;
+            }
+
+            // Wait for a worker to finish, for a delayed account to come due, or to refresh the embed
+            tokio::select! {
+                finished = workers.join_next() => {
+                    match finished {
+                        Some(Ok(WorkerSignal::StopQueue)) => halted = true,
+                        // Handout runs skip accounts already in `attempts`, so the refused
+                        // session is taken back off the count.
+                        Some(Ok(WorkerSignal::Requeue(name))) => {
+                            if let Some(count) = attempts.get_mut(&name) {
+                                *count -= 1;
+                                if *count == 0 {
+                                    attempts.remove(&name);
+                                }
+                            }
+                        },
+                        _ => {},
+                    }
+                }
+                _ = tokio::time::sleep(until(wake_at, 30)), if wake_at.is_some() => {}
+                _ = refresh.tick(), if status_msg.is_some() => {}
+            }
+
+            if let Some(msg) = status_msg.as_mut() {
+                let embed = {
+                    let db = db_clone.lock().await;
+                    embeds::queue_status(&QueueSnapshot::collect(&db, &pool, &kind, &attempts, Utc::now()), &kind, Some(started), false)
+                };
+                let _ = msg.edit(&http_clone, EditMessage::new().embed(embed)).await;
+            }
+            // Small delay to prevent tight loops in edge cases
+            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        }
+
+        pool.finish_manager(&key);
+        if let Some(msg) = status_msg.as_mut() {
+            let embed = {
+                let db = db_clone.lock().await;
+                embeds::queue_status(&QueueSnapshot::collect(&db, &pool, &kind, &attempts, Utc::now()), &kind, Some(started), true)
+            };
+            let _ = msg.edit(&http_clone, EditMessage::new().embed(embed)).await;
+        }
+    });
+}
+
+/// Force-runs one account's daily session in the background, outside any queue. It is
+/// registered like a queue manager, so /force_stop_all halts it before its session starts.
+pub fn force_run(db_clone: Arc<Mutex<Database>>, pool: Arc<WorkerPool>, http_clone: Arc<Http>, channel_id: ChannelId, name: String) {
+    tokio::spawn(async move {
+        let key = format!("force:{}", name);
+        let Some(stop) = pool.start_manager(&key) else {
+            let _ = channel_id.say(&http_clone, format!("[WARN] **{}** is already running.", name)).await;
+            return;
+        };
+        force_run_claimed(&db_clone, &pool, &http_clone, channel_id, &name, &stop).await;
+        pool.finish_manager(&key);
+    });
+}
+
+async fn force_run_claimed(db_clone: &Arc<Mutex<Database>>, pool: &Arc<WorkerPool>, http_clone: &Arc<Http>, channel_id: ChannelId, name: &str, stop: &CancellationToken) {
+    let (acc, limit, config) = {
+        let db = db_clone.lock().await;
+        (db.data.accounts.iter().find(|a| a.name == name).cloned(),
+         db.concurrency(),
+         ClientConfig::resolve(db.data.settings.client.as_ref()))
+    };
+    let Some(acc) = acc else {
+        let _ = channel_id.say(http_clone, format!("[ERROR] Account **{}** not found.", name)).await;
+        return;
+    };
+
+    let lease = match pool.try_claim(&acc.name, RunMode::Daily, limit) {
+        Ok(lease) => lease,
+        Err(ClaimError::AlreadyRunning) => {
+            let _ = channel_id.say(http_clone, format!("[WARN] **{}** is already running.", acc.name)).await;
+            return;
+        },
+        Err(ClaimError::PoolFull) => {
+            let _ = channel_id.say(http_clone, "[WARN] All workers are busy. Try again later.").await;
+            return;
+        },
+        Err(ClaimError::Reserved) => {
+            let _ = channel_id.say(http_clone, "[WARN] A cookie check is running. Try again shortly.").await;
+            return;
+        },
+    };
+    // Once claimed, /force_stop_all cancels the lease itself
+    if stop.is_cancelled() {
+        let _ = channel_id.say(http_clone, format!("[INFO] Force run of **{}** was stopped before it started.", acc.name)).await;
+        return;
+    }
+
+    let picked = db_clone.lock().await.pick_cookie();
+    if let Some((cookie_label, cookie)) = picked {
+        let _ = channel_id.say(http_clone, format!("[INFO] Force running **{}**...", acc.name)).await;
+        // Same handling as a queue run, so failures count against the retry budget
+        let job = WorkerJob { acc, mode: RunMode::Daily, cookie_label, cookie, config, retries: 0, lease };
+        run_account(Arc::clone(db_clone), Arc::clone(http_clone), Some(channel_id), job).await;
+    } else {
+        let _ = channel_id.say(http_clone, "[ERROR] No healthy session cookies. Use /set_cookies.").await;
+    }
+}
+
+/// One session for one account. The job's lease keeps the account reserved until this returns.
+pub async fn run_account(db_clone: Arc<Mutex<Database>>, http_clone: Arc<Http>, source_channel: Option<ChannelId>, job: WorkerJob) -> WorkerSignal {
+    let WorkerJob { acc, mode, cookie_label, cookie, config, retries, lease } = job;
+    let started = Utc::now();
+    if mode == RunMode::Handout {
+        if let Some(chan) = source_channel {
+             let _ = chan.say(&http_clone, format!("[INFO] Handout: Processing **{}**...", acc.name)).await;
+        }
+    }
+
+    let decrypted = db_clone.lock().await.decrypt_code(&acc);
+    let decrypted_code = match decrypted {
+        Ok(code) => code,
+        Err(e) => {
+            undecryptable_code(&db_clone, &http_clone, source_channel, &acc.name, &e).await;
+            return WorkerSignal::Continue;
+        }
+    };
+
+    let mut signal = WorkerSignal::Continue;
+    match EvertextClient::connect(&config, &cookie).await {
+        Ok(mut client) => {
+            keep_rotated_cookie(&db_clone, &cookie_label, client.rotated_cookie()).await;
+            client.report_to(lease.progress());
+            let result = client.run_loop(&acc, &decrypted_code, mode, lease.cancel_token()).await;
+            record_run(&db_clone, &acc, mode, started, retries, Some(&result)).await;
+            if result.is_ok() {
+                let _ = db_clone.lock().await.cookie_validated(&cookie_label);
+            }
+            match (mode, result) {
+                (_, Err(e)) if e.kind == SessionErrorKind::LoginRequired => {
+                    signal = match quarantine_cookie(&db_clone, &http_clone, source_channel, &cookie_label).await {
+                        WorkerSignal::Continue => WorkerSignal::Requeue(acc.name.clone()),
+                        other => other,
+                    };
+                },
+                (_, Err(e)) if e.kind == SessionErrorKind::Cancelled => {
+                    if let Some(chan) = source_channel {
+                        let _ = chan.say(&http_clone, format!("[INFO] **{}** stopped.", acc.name)).await;
+                    }
+                },
+                (RunMode::Handout, Ok(SessionOutcome::Completed(_))) => {
+                    if let Some(chan) = source_channel {
+                        let _ = chan.say(&http_clone, format!("[SUCCESS] Handout **{}** completed.", acc.name)).await;
+                    }
+                },
+                (RunMode::Handout, Err(e)) => {
+                    if let Some(chan) = source_channel {
+                        let _ = chan.say(&http_clone, format!("[ERROR] Handout **{}** failed: {}", acc.name, e)).await;
+                    }
+                },
+                (RunMode::Daily, Ok(SessionOutcome::Completed(session))) => {
+                    {
+                        let mut db = db_clone.lock().await;
+                        let _ = db.update_status(&acc.name, "done");
+                    }
+                    if let Some(chan) = source_channel {
+                        let _ = chan.say(&http_clone, format!("[SUCCESS] **{}** completed.", acc.name)).await;
+                    }
+                    log_message(Arc::clone(&db_clone), Arc::clone(&http_clone), format!("[SUCCESS] Automation: **{}** completed successfully in {}s.", session.account, session.elapsed.as_secs()), source_channel).await;
+                },
+                (RunMode::Daily, Err(e)) => {
+                    retry_later(&db_clone, &http_clone, source_channel, &acc, Some(e.kind), &e.to_string()).await;
+                },
+            }
+        },
+        Err(e) => {
+            record_run(&db_clone, &acc, mode, started, retries, None).await;
+            if mode == RunMode::Daily {
+                retry_later(&db_clone, &http_clone, source_channel, &acc, None, &e.to_string()).await;
+            } else {
+                if let Some(chan) = source_channel {
+                    let _ = chan.say(&http_clone, format!("[ERROR] Connection failed for **{}**: {}", acc.name, e)).await;
+                }
+                tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+            }
+        }
+    }
+
+    if mode == RunMode::Handout {
+        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+    }
+    signal
+}
+
+/// Marks an account whose restore code doesn't decrypt as failed instead of starting it:
+/// typing ciphertext into the game only ends in a confusing Zigza error.
+async fn undecryptable_code(db_clone: &Arc<Mutex<Database>>, http_clone: &Arc<Http>, source_channel: Option<ChannelId>, name: &str, error: &CryptoError) {
+    println!("[ERROR] Restore code of {} cannot be decrypted: {}", name, error);
+    let _ = db_clone.lock().await.update_status(name, "error: DECRYPT_FAILED");
+    let message = format!("❌ **{}**: the restore code cannot be decrypted ({}). Check ENCRYPTION_KEY or re-add the account.", name, error);
+    if let Some(chan) = source_channel {
+        let _ = chan.say(http_clone, &message).await;
+    }
+    log_message(Arc::clone(db_clone), Arc::clone(http_clone), message, source_channel).await;
+}
+
+/// Takes a cookie the game refused out of rotation. The account isn't charged a retry;
+/// the queue picks it up again with the next healthy cookie, and stops once none are left.
+async fn quarantine_cookie(db_clone: &Arc<Mutex<Database>>, http_clone: &Arc<Http>, source_channel: Option<ChannelId>, label: &str) -> WorkerSignal {
+    let healthy = {
+        let mut db = db_clone.lock().await;
+        if let Err(e) = db.quarantine_cookie(label) {
+            println!("[ERROR] Failed to quarantine cookie {}: {}", label, e);
+        }
+        db.healthy_cookies()
+    };
+
+    if healthy == 0 {
+        if let Some(chan) = source_channel {
+            let _ = chan.say(http_clone, format!("⚠️ **CRITICAL: Session cookie `{}` expired and no healthy cookies are left!** Stopping queue.", label)).await;
+        }
+        log_message(Arc::clone(db_clone), Arc::clone(http_clone), format!("⚠️ **[CRITICAL] Automation: Session cookie `{}` expired and no healthy cookies are left!** Stopping queue.", label), source_channel).await;
+        WorkerSignal::StopQueue
+    } else {
+        if let Some(chan) = source_channel {
+            let _ = chan.say(http_clone, format!("[WARN] Session cookie `{}` was refused and is quarantined. Continuing with {} healthy cookie(s).", label, healthy)).await;
+        }
+        log_message(Arc::clone(db_clone), Arc::clone(http_clone), format!("⚠️ Automation: Session cookie `{}` was refused (login required) and is quarantined. {} healthy cookie(s) left.", label, healthy), source_channel).await;
+        WorkerSignal::Continue
+    }
+}
+
+/// Counts a failed daily session against the account's retry budget and reports it.
+/// The account's `nextAttemptAt` keeps the queue from picking it up again before the
+/// backoff is over. `kind` is None when the connection never came up.
+async fn retry_later(db_clone: &Arc<Mutex<Database>>, http_clone: &Arc<Http>, source_channel: Option<ChannelId>, acc: &Account, kind: Option<SessionErrorKind>, reason: &str) {
+    let Some(class) = RetryClass::of(kind) else { return };
+    let code = kind.map_or("CONNECT_FAILED", |k| k.code());
+    let decision = {
+        let mut db = db_clone.lock().await;
+        db.record_failure(&acc.name, class, code)
+    };
+    let (attempt, max, delay) = match decision {
+        Ok(decision) => decision,
+        Err(e) => {
+            println!("[ERROR] Failed to record retry for {}: {}", acc.name, e);
+            return;
+        }
+    };
+
+    let Some(delay) = delay else {
+        if let Some(chan) = source_channel {
+            let _ = chan.say(http_clone, format!("[ERROR] **{}** gave up after {} attempts ({}): {}", acc.name, attempt, code, reason)).await;
+        }
+        log_message(Arc::clone(db_clone), Arc::clone(http_clone), format!("[ERROR] Automation: **{}** gave up after {} attempts ({}). Last error: {}", acc.name, attempt, code, reason), source_channel).await;
+        return;
+    };
+
+    let wait = retry::describe(delay);
+    let (channel_msg, log_msg) = match class {
+        RetryClass::InvalidCommand => (format!("[WARN] Invalid Command on **{}**. Restarting session in {} ({}/{}).", acc.name, wait, attempt, max), None),
+        RetryClass::Zigza => (
+            format!("[WARN] Zigza error on **{}**. Waiting {} before retry ({}/{}).", acc.name, wait, attempt, max),
+            Some(format!("[WARN] Automation: Zigza detected on **{}**. Retrying in {}.", acc.name, wait)),
+        ),
+        RetryClass::ServerFull => (
+            format!("[WARN] Server Full. Retrying **{}** in {} ({}/{}).", acc.name, wait, attempt, max),
+            Some(format!("[WARN] Automation: Server full. Retrying **{}** in {}.", acc.name, wait)),
+        ),
+        RetryClass::Connection => (format!("[WARN] Connection issue on **{}** (Reason: {}). Retrying in {} ({}/{})...", acc.name, reason, wait, attempt, max), None),
+        RetryClass::Transport => (
+            format!("[ERROR] **{}** failed: {}. Retrying in {} ({}/{}).", acc.name, reason, wait, attempt, max),
+            Some(format!("[ERROR] Automation: **{}** failed. Reason: {}. Retrying in {}.", acc.name, reason, wait)),
+        ),
+    };
+    if let Some(chan) = source_channel {
+        let _ = chan.say(http_clone, channel_msg).await;
+    }
+    if let Some(log_msg) = log_msg {
+        log_message(Arc::clone(db_clone), Arc::clone(http_clone), log_msg, source_channel).await;
+    }
+}
+
+/// Writes a history entry for a finished session. `None` means the connection never came up.
+async fn record_run(db: &Arc<Mutex<Database>>, acc: &Account, mode: RunMode, started: DateTime<Utc>, retries: u32, result: Option<&SessionResult>) {
+    let record = RunRecord::new(acc, mode, started, retries, result);
+    let mut db = db.lock().await;
+    if let Err(e) = db.record_run(record) {
+        println!("[WARN] Failed to record run history for {}: {}", acc.name, e);
+    }
+}
+
+/// Executes one scheduler job. Queue runs are spawned so they don't hold up the scheduler.
+pub async fn run_job(db: Arc<Mutex<Database>>, pool: Arc<WorkerPool>, http: Arc<Http>, job: Job) {
+    match job {
+        Job::DailyReset => {
+            let mut db = db.lock().await;
+            let now = Utc::now();
+            db.data.settings.last_reset_date = scheduler::current_game_day(&db.data.settings, now)
+                .or_else(|| Some(now.format("%Y-%m-%d").to_string()));
+            let _ = db.save_settings();
+            match db.reset_all_statuses() {
+                Ok(_) => println!("[INFO] Scheduler: All accounts reset to pending."),
+                Err(e) => println!("[ERROR] Scheduler: Daily reset failed: {}", e),
+            }
+        },
+        Job::DailyRun => spawn_manager(db, pool, http, QueueKind::Daily(None), None),
+        Job::HandoutRun => spawn_manager(db, pool, http, QueueKind::Handout, None),
+        Job::CookieRefresh => {
+            let (cookies, config) = {
+                let db = db.lock().await;
+                let healthy: Vec<_> = db.data.settings.session_cookies.iter()
+                    .filter(|c| !c.quarantined)
+                    .filter_map(|c| Some((c.label.clone(), db.cookie_secret(c).ok()?)))
+                    .collect();
+                (healthy, ClientConfig::resolve(db.data.settings.client.as_ref()))
+            };
+            if cookies.is_empty() {
+                println!("[WARN] Scheduler: Cookie refresh skipped, no healthy cookie set.");
+            }
+            for (label, cookie) in cookies {
+                match do_http_refresh(&config, &cookie).await {
+                    Ok(refresh) => keep_rotated_cookie(&db, &label, refresh.rotated.as_deref()).await,
+                    Err(e) => println!("[WARN] Scheduler: Cookie refresh failed for '{}': {}", label, e),
+                }
+            }
+        },
+        Job::CookieCheck => {
+            // The probe stops whatever game the cookie is running, so never check mid-session
+            let Some(reservation) = pool.reserve_all() else {
+                println!("[INFO] Scheduler: Cookie check skipped, sessions are running.");
+                return;
+            };
+            tokio::spawn(async move {
+                check_cookies(&db, &http, None).await;
+                drop(reservation);
+            });
+        },
+        Job::Backup => {
+            let dir = db::backup_dir();
+            let db = db.lock().await;
+            match db.backup(&dir) {
+                Ok(path) => println!("[INFO] Scheduler: Database backed up to {}", path),
+                Err(e) => println!("[ERROR] Scheduler: Backup failed: {}", e),
+            }
+        },
+    }
+}
+
+/// Starts the scheduler loop: alerts admins about undecryptable secrets, catches up on a
+/// missed daily reset, then runs the jobs in `settings.schedules` as they come due.
+pub fn spawn_scheduler(db_clone: Arc<Mutex<Database>>, pool_clone: Arc<WorkerPool>, http: Arc<Http>) {
+    tokio::spawn(async move {
+        // Secrets the current ENCRYPTION_KEY can't open (or left in plaintext) would fail every run they're used in
+        let secrets = db_clone.lock().await.check_secrets();
+        if !secrets.is_ok() {
+            let alert = format!(
+                "🔐 **Stored secrets are unusable with the current ENCRYPTION_KEY**\n{}\nListed accounts will not be run and listed cookies are skipped. Set or restore the key (then run `encrypt-codes` for plaintext), or re-add them.",
+                secrets.summary()
+            );
+            alert_admins(&db_clone, &http, alert).await;
+        }
+
+        let missed = {
+            let mut db = db_clone.lock().await;
+            let added = scheduler::ensure_defaults(&mut db.data.settings);
+            let missed = scheduler::take_missed_reset(&mut db.data.settings, Utc::now());
+            if added || missed.is_some() {
+                let _ = db.save_settings();
+            }
+            if missed.is_some() {
+                if let Err(e) = db.reset_all_statuses() {
+                    println!("[ERROR] Scheduler: Catch-up reset failed: {}", e);
+                }
+            }
+            missed
+        };
+
+        // The bot was down when the daily reset was due: reset now and run the day's queue
+        if let Some(missed) = missed {
+            let since = missed.previous.as_deref().unwrap_or("never");
+            println!("[INFO] Scheduler: Missed daily reset for {} (last reset: {}). Catching up.", missed.game_day, since);
+            let mut report = format!("⏰ **Missed daily reset caught up** for game day {} (last reset: {}). All accounts reset to pending.", missed.game_day, since);
+            if missed.run_daily {
+                report.push_str(" Starting the daily run...");
+            }
+            log_message(Arc::clone(&db_clone), Arc::clone(&http), report, None).await;
+
+            if missed.run_daily {
+                run_job(Arc::clone(&db_clone), Arc::clone(&pool_clone), Arc::clone(&http), Job::DailyRun).await;
+            }
+        }
+
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(30));
+        loop {
+            interval.tick().await;
+            let due = {
+                let mut db = db_clone.lock().await;
+                let (due, changed) = scheduler::take_due(&mut db.data.settings, Utc::now());
+                if changed {
+                    if let Err(e) = db.save_settings() {
+                        println!("[ERROR] Scheduler: Failed to persist next run times: {}", e);
+                    }
+                }
+                due
+            };
+
+            for job in due {
+                println!("[INFO] Scheduler: Running job '{}' at {} UTC", job.name(), Utc::now());
+                run_job(Arc::clone(&db_clone), Arc::clone(&pool_clone), Arc::clone(&http), job).await;
+            }
+        }
+    });
+}
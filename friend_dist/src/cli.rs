@@ -0,0 +1,837 @@
+//! Command line entry points. Without a subcommand the binary starts the Discord bot;
+//! the subcommands work on the same database without Discord, e.g. from cron.
+
+use clap::{Parser, Subcommand, ValueEnum};
+use tokio_util::sync::CancellationToken;
+
+use std::io::BufRead;
+
+use evertext_bot_rust::crypto::{self, Cipher};
+use evertext_bot_rust::db::{self, Account, Database, DbData, LoadMode, NextMode, RunRecord};
+use evertext_bot_rust::maintenance::{self, Severity};
+use evertext_bot_rust::protocol::config::ClientConfig;
+use evertext_bot_rust::protocol::socket::{EvertextClient, RunMode, SessionErrorKind, SessionOutcome};
+use evertext_bot_rust::retry::{self, RetryClass};
+use evertext_bot_rust::storage::json::JsonStorage;
+use evertext_bot_rust::storage::sqlite::SqliteStorage;
+use evertext_bot_rust::storage::Storage;
+
+/// Every requested run completed (or there was nothing to do).
+pub const EXIT_OK: i32 = 0;
+/// At least one session failed, or `db validate` found errors.
+pub const EXIT_RUN_FAILED: i32 = 1;
+/// Bad arguments or an unknown account (clap also uses 2 for usage errors).
+pub const EXIT_USAGE: i32 = 2;
+/// The database, encryption key or cookie pool is unusable.
+pub const EXIT_SETUP: i32 = 3;
+/// The game refused every session cookie (login required).
+pub const EXIT_LOGIN_REQUIRED: i32 = 4;
+/// Interrupted with Ctrl-C.
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+#[derive(Parser)]
+#[command(name = "evertext_bot_rust", about = "EverText automation. Without a subcommand, starts the Discord bot.")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Play sessions in the terminal, one account after another
+    Run {
+        /// Account to run
+        #[arg(long, required_unless_present = "all", conflicts_with = "all")]
+        account: Option<String>,
+        /// Every account the bot's queue would pick up for this mode
+        #[arg(long)]
+        all: bool,
+        #[arg(long, value_enum, default_value_t = ModeArg::Daily)]
+        mode: ModeArg,
+    },
+    /// List accounts and their status
+    List,
+    /// Queue, cookie pool and schedule overview, or the details of one account
+    Status {
+        #[arg(long)]
+        account: Option<String>,
+    },
+    /// One-shot import of a db.json into a SQLite database
+    ImportJson {
+        #[arg(default_value = "db.json")]
+        json: String,
+        #[arg(default_value = "db.sqlite")]
+        sqlite: String,
+    },
+    /// Re-encrypt every stored secret under a new ENCRYPTION_KEY and write it to .env
+    RotateKey {
+        /// Current key; read from stdin when omitted, so it stays out of the shell history
+        old_key: Option<String>,
+        /// New key; read from stdin when omitted
+        new_key: Option<String>,
+    },
+    /// Edit, check and merge database files
+    #[command(subcommand)]
+    Db(DbCommand),
+}
+
+/// Secrets (`--code`, cookie values) are read from stdin when left out, so they stay out
+/// of the shell history.
+#[derive(Subcommand)]
+pub enum DbCommand {
+    /// Add an account, or replace the one with the same name
+    AddAccount {
+        name: String,
+        /// Restore code; read from stdin when omitted
+        #[arg(long)]
+        code: Option<String>,
+        #[arg(long)]
+        server: Option<String>,
+        /// Discord user ID of the owner
+        #[arg(long)]
+        owner: Option<String>,
+        #[arg(long)]
+        handout: bool,
+    },
+    RemoveAccount {
+        name: String,
+    },
+    /// Change fields of an account; fields that aren't given stay as they are
+    EditAccount {
+        name: String,
+        /// New restore code; `-` reads it from stdin
+        #[arg(long)]
+        code: Option<String>,
+        #[arg(long)]
+        server: Option<String>,
+        #[arg(long)]
+        owner: Option<String>,
+        #[arg(long)]
+        status: Option<String>,
+        #[arg(long)]
+        handout: Option<bool>,
+        #[arg(long)]
+        ping: Option<bool>,
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=3))]
+        potion_slot: Option<u8>,
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..=10))]
+        refill_count: Option<u32>,
+        /// -1 for unlimited
+        #[arg(long, value_parser = clap::value_parser!(i64).range(-1..=20), allow_negative_numbers = true)]
+        max_refills: Option<i64>,
+        #[arg(long)]
+        spend_on_events: Option<bool>,
+        #[arg(long, value_parser = ["auto", "manual"])]
+        next_mode: Option<String>,
+    },
+    /// Encrypt plaintext (and old-format) restore codes and cookies with ENCRYPTION_KEY
+    EncryptCodes,
+    /// Set every account back to pending with a fresh retry budget
+    ResetStatuses,
+    /// Add or replace a session cookie in the pool
+    SetCookie {
+        label: String,
+        /// Cookie value; read from stdin when omitted
+        value: Option<String>,
+    },
+    /// Show the owner, admin role and bot admins
+    Admins,
+    /// Check a database against the schema without changing it (default: the configured database)
+    Validate {
+        file: Option<String>,
+    },
+    /// Merge OTHER into BASE (both db.json files). BASE wins on conflicts, which are listed.
+    Merge {
+        base: String,
+        other: String,
+        /// Where to write the result
+        #[arg(long, required_unless_present = "dry_run")]
+        out: Option<String>,
+        /// ENCRYPTION_KEY of OTHER, if it differs from the current one
+        #[arg(long)]
+        other_key: Option<String>,
+        /// Only print the report
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ModeArg {
+    Daily,
+    Handout,
+}
+
+impl From<ModeArg> for RunMode {
+    fn from(mode: ModeArg) -> Self {
+        match mode {
+            ModeArg::Daily => RunMode::Daily,
+            ModeArg::Handout => RunMode::Handout,
+        }
+    }
+}
+
+/// Runs a subcommand and returns the process exit code.
+pub async fn run(command: Command) -> i32 {
+    match command {
+        Command::ImportJson { json, sqlite } => match Database::import_json(&json, &sqlite) {
+            Ok(count) => {
+                println!("[INFO] Imported {} accounts from {} into {}", count, json, sqlite);
+                EXIT_OK
+            },
+            Err(e) => {
+                println!("[CRITICAL] Import failed: {}", e);
+                EXIT_SETUP
+            },
+        },
+        Command::RotateKey { old_key, new_key } => {
+            let keys = secret_arg(old_key, "current encryption key")
+                .and_then(|old| secret_arg(new_key, "new encryption key").map(|new| (old, new)));
+            let (old_key, new_key) = match keys {
+                Ok(keys) => keys,
+                Err(e) => {
+                    println!("[ERROR] {}", e);
+                    return EXIT_USAGE;
+                },
+            };
+            let rotated = Database::load()
+                .and_then(|mut db| db.rotate_key(&Cipher::new(&old_key), &Cipher::new(&new_key), &db::backup_dir()));
+            match rotated {
+                Ok(r) => {
+                    println!("[INFO] Re-encrypted {} restore code(s) and {} cookie(s). Backup: {}", r.accounts, r.cookies, r.backup);
+                    match crypto::store_env_key(std::path::Path::new(crypto::ENV_FILE), &new_key) {
+                        Ok(()) => println!("[INFO] ENCRYPTION_KEY in {} now holds the new key.", crypto::ENV_FILE),
+                        Err(e) => println!("[WARN] Could not update {} ({}). Set ENCRYPTION_KEY to the new key before the next start.", crypto::ENV_FILE, e),
+                    }
+                    EXIT_OK
+                },
+                Err(e) => {
+                    println!("[CRITICAL] {}", e);
+                    EXIT_SETUP
+                },
+            }
+        },
+        Command::Db(DbCommand::Validate { file }) => validate(file),
+        Command::Db(DbCommand::Merge { base, other, out, other_key, dry_run }) => merge(&base, &other, out.as_deref(), other_key.as_deref(), dry_run),
+        command => {
+            // Commands that add a cookie or account must work before the first cookie is stored
+            let mode = match (&command, LoadMode::from_env()) {
+                (Command::Db(DbCommand::SetCookie { .. } | DbCommand::AddAccount { .. }), LoadMode::Strict) => LoadMode::CookieOptional,
+                (_, mode) => mode,
+            };
+            let mut db = match Database::load_with(mode) {
+                Ok(db) => db,
+                Err(e) => {
+                    println!("[CRITICAL] Failed to load database: {}", e);
+                    return EXIT_SETUP;
+                },
+            };
+            match command {
+                Command::Run { account, mode, .. } => run_accounts(&mut db, account.as_deref(), mode.into()).await,
+                Command::List => list(&db),
+                Command::Status { account } => status(&db, account.as_deref()),
+                Command::Db(action) => admin(&mut db, action),
+                Command::ImportJson { .. } | Command::RotateKey { .. } => unreachable!("handled above"),
+            }
+        },
+    }
+}
+
+/// `account`, or everything the bot's queue would run in `mode` when None.
+async fn run_accounts(db: &mut Database, account: Option<&str>, mode: RunMode) -> i32 {
+    let targets: Vec<Account> = match account {
+        Some(name) => match db.data.accounts.iter().find(|a| a.name == name) {
+            Some(acc) => vec![acc.clone()],
+            None => {
+                println!("[ERROR] No account named '{}'. See `list`.", name);
+                return EXIT_USAGE;
+            },
+        },
+        None => match mode {
+            RunMode::Daily => db.data.accounts.iter().filter(|a| a.needs_daily_run()).cloned().collect(),
+            RunMode::Handout => db.get_handout_accounts(),
+        },
+    };
+    if targets.is_empty() {
+        println!("[INFO] Nothing to run.");
+        return EXIT_OK;
+    }
+
+    let cancel = CancellationToken::new();
+    let on_interrupt = cancel.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            println!("[WARN] Interrupted, stopping the current session...");
+            on_interrupt.cancel();
+        }
+    });
+    run_targets(db, targets, mode, &cancel).await
+}
+
+/// Plays `targets` one after another until done or `cancel` fires.
+async fn run_targets(db: &mut Database, targets: Vec<Account>, mode: RunMode, cancel: &CancellationToken) -> i32 {
+    let config = ClientConfig::resolve(db.data.settings.client.as_ref());
+    let total = targets.len();
+    let mut failed = Vec::new();
+    for (i, acc) in targets.iter().enumerate() {
+        if cancel.is_cancelled() {
+            return EXIT_INTERRUPTED;
+        }
+        println!("[INFO] ({}/{}) {} run for {}", i + 1, total, mode.as_str(), acc.name);
+
+        let code = match db.decrypt_code(acc) {
+            Ok(code) => code,
+            Err(e) => {
+                println!("[ERROR] Restore code of {} cannot be decrypted: {}", acc.name, e);
+                let _ = db.update_status(&acc.name, "error: DECRYPT_FAILED");
+                failed.push(acc.name.clone());
+                continue;
+            },
+        };
+        let Some((cookie_label, cookie)) = db.pick_cookie() else {
+            println!("[CRITICAL] No healthy session cookie left.");
+            return EXIT_LOGIN_REQUIRED;
+        };
+
+        let started = chrono::Utc::now();
+        let result = match EvertextClient::connect(&config, &cookie).await {
+            Ok(mut client) => {
+                if let Some(rotated) = client.rotated_cookie() {
+                    match db.rotate_cookie(&cookie_label, rotated) {
+                        Ok(true) => println!("[INFO] Session cookie '{}' was rotated by the server and saved.", cookie_label),
+                        Ok(false) => {},
+                        Err(e) => println!("[ERROR] Failed to save rotated session cookie '{}': {}", cookie_label, e),
+                    }
+                }
+                Some(client.run_loop(acc, &code, mode, cancel).await)
+            },
+            Err(e) => {
+                println!("[ERROR] Connection failed for {}: {}", acc.name, e);
+                None
+            },
+        };
+        if let Err(e) = db.record_run(RunRecord::new(acc, mode, started, 0, result.as_ref())) {
+            println!("[WARN] Failed to record run history for {}: {}", acc.name, e);
+        }
+
+        match result {
+            Some(Ok(SessionOutcome::Completed(session))) => {
+                let _ = db.cookie_validated(&cookie_label);
+                if mode == RunMode::Daily {
+                    let _ = db.update_status(&acc.name, "done");
+                }
+                println!("[SUCCESS] {} completed in {}s.", acc.name, session.elapsed.as_secs());
+            },
+            Some(Err(e)) if e.kind == SessionErrorKind::Cancelled => {
+                // A stopped handout run leaves the account's daily alone
+                if mode == RunMode::Daily {
+                    let _ = db.update_status(&acc.name, "stopped");
+                }
+                return EXIT_INTERRUPTED;
+            },
+            Some(Err(e)) if e.kind == SessionErrorKind::LoginRequired => {
+                let _ = db.quarantine_cookie(&cookie_label);
+                println!("[ERROR] Session cookie '{}' was refused (login required) and is quarantined.", cookie_label);
+                failed.push(acc.name.clone());
+            },
+            other => {
+                let kind = match &other {
+                    Some(Err(e)) => {
+                        println!("[ERROR] {} failed: {}", acc.name, e);
+                        Some(e.kind)
+                    },
+                    _ => None,
+                };
+                if mode == RunMode::Daily {
+                    if let Some(class) = RetryClass::of(kind) {
+                        let code = kind.map_or("CONNECT_FAILED", |k| k.code());
+                        if let Ok((attempt, max, delay)) = db.record_failure(&acc.name, class, code) {
+                            match delay {
+                                Some(delay) => println!("[INFO] Attempt {}/{}; the bot retries {} in {}.", attempt, max, acc.name, retry::describe(delay)),
+                                None => println!("[INFO] {} gave up after {} attempts.", acc.name, attempt),
+                            }
+                        }
+                    }
+                }
+                failed.push(acc.name.clone());
+            },
+        }
+    }
+
+    if failed.is_empty() {
+        println!("[INFO] All {} run(s) completed.", total);
+        EXIT_OK
+    } else {
+        println!("[WARN] {} of {} run(s) failed: {}", failed.len(), total, failed.join(", "));
+        if db.healthy_cookies() == 0 { EXIT_LOGIN_REQUIRED } else { EXIT_RUN_FAILED }
+    }
+}
+
+fn list(db: &Database) -> i32 {
+    if db.data.accounts.is_empty() {
+        println!("No accounts registered.");
+        return EXIT_OK;
+    }
+    println!("{:<20} {:<30} {:<8} {:<25} OWNER", "NAME", "STATUS", "HANDOUT", "LAST RUN");
+    for acc in &db.data.accounts {
+        println!(
+            "{:<20} {:<30} {:<8} {:<25} {}",
+            acc.name,
+            acc.status,
+            if acc.handout_enabled { "yes" } else { "no" },
+            acc.last_run.as_deref().unwrap_or("-"),
+            acc.username.as_deref().or(acc.user_id.as_deref()).unwrap_or("-")
+        );
+    }
+    EXIT_OK
+}
+
+fn status(db: &Database, account: Option<&str>) -> i32 {
+    if let Some(name) = account {
+        let Some(acc) = db.data.accounts.iter().find(|a| a.name == name) else {
+            println!("[ERROR] No account named '{}'. See `list`.", name);
+            return EXIT_USAGE;
+        };
+        println!("{}: {}", acc.name, acc.status);
+        println!("  last run:      {}", acc.last_run.as_deref().unwrap_or("never"));
+        println!("  target server: {}", acc.target_server.as_deref().unwrap_or("-"));
+        println!("  handout:       {}", if acc.handout_enabled { "enabled" } else { "disabled" });
+        if let Some(at) = &acc.next_attempt_at {
+            println!("  next attempt:  {}", at);
+        }
+        for (class, count) in &acc.retry_counts {
+            println!("  retries ({}): {}", class, count);
+        }
+        println!("  recent runs:");
+        for run in db.data.history.iter().rev().filter(|r| r.account == acc.name).take(5) {
+            println!("    {} {:<8} {:<22} {}", run.started_at, run.mode, run.outcome, run.final_prompt.as_deref().unwrap_or(""));
+        }
+        return EXIT_OK;
+    }
+
+    let count = |pred: &dyn Fn(&Account) -> bool| db.data.accounts.iter().filter(|a| pred(a)).count();
+    println!("Accounts: {} total", db.data.accounts.len());
+    println!("  done:     {}", count(&|a| a.status == "done"));
+    println!("  pending:  {}", count(&|a| a.status == "pending"));
+    println!("  retrying: {}", count(&|a| a.status.contains("Retrying")));
+    println!("  failed:   {}", count(&|a| a.status.starts_with("error") && !a.status.contains("Retrying")));
+    println!("  stopped:  {}", count(&|a| a.status == "stopped"));
+    println!("Last reset: {}", db.data.settings.last_reset_date.as_deref().unwrap_or("never"));
+
+    println!("Session cookies ({} healthy):", db.healthy_cookies());
+    for cookie in &db.data.settings.session_cookies {
+        println!(
+            "  {:<12} {:<12} validated {}, {} failure(s)",
+            cookie.label,
+            if cookie.quarantined { "quarantined" } else { "healthy" },
+            cookie.last_validated.as_deref().unwrap_or("never"),
+            cookie.failure_count
+        );
+    }
+
+    println!("Schedules:");
+    for job in &db.data.settings.schedules {
+        let next = if job.enabled { job.next_run.as_deref().unwrap_or("not computed yet") } else { "disabled" };
+        println!("  {:<15} {:<15} {:<15} next: {}", job.name, job.cron, job.timezone, next);
+    }
+    EXIT_OK
+}
+
+/// `given`, or one line from stdin.
+fn secret_arg(given: Option<String>, what: &str) -> Result<String, String> {
+    let value = match given.filter(|v| v != "-") {
+        Some(v) => v,
+        None => {
+            println!("Enter the {} and press Enter:", what);
+            let mut line = String::new();
+            std::io::stdin().lock().read_line(&mut line).map_err(|e| e.to_string())?;
+            line
+        },
+    };
+    let value = value.trim().to_string();
+    if value.is_empty() {
+        return Err(format!("empty {}", what));
+    }
+    Ok(value)
+}
+
+/// Prints the error and maps it to an exit code.
+fn report<T>(result: Result<T, Box<dyn std::error::Error + Send + Sync>>) -> Result<T, i32> {
+    result.map_err(|e| {
+        println!("[ERROR] {}", e);
+        EXIT_SETUP
+    })
+}
+
+/// The `db` subcommands that edit the configured database.
+fn admin(db: &mut Database, action: DbCommand) -> i32 {
+    let result = match action {
+        DbCommand::AddAccount { name, code, server, owner, handout } => add_account(db, name, code, server, owner, handout),
+        DbCommand::RemoveAccount { name } => match report(db.remove_account(&name)) {
+            Ok(true) => {
+                println!("[INFO] Removed account {}.", name);
+                Ok(())
+            },
+            Ok(false) => {
+                println!("[ERROR] No account named '{}'.", name);
+                Err(EXIT_USAGE)
+            },
+            Err(code) => Err(code),
+        },
+        DbCommand::EditAccount { name, code, server, owner, status, handout, ping, potion_slot, refill_count, max_refills, spend_on_events, next_mode } => {
+            let code = match code {
+                Some(code) => match secret_arg(Some(code), "restore code").map(|c| db.encrypt_secret(&c)) {
+                    Ok(Ok(encrypted)) => Some(encrypted),
+                    Ok(Err(e)) => {
+                        println!("[ERROR] Cannot encrypt the restore code: {}", e);
+                        return EXIT_SETUP;
+                    },
+                    Err(e) => {
+                        println!("[ERROR] {}", e);
+                        return EXIT_USAGE;
+                    },
+                },
+                None => None,
+            };
+            let edited = db.edit_account(&name, |acc| {
+                if let Some(v) = code { acc.code = v; }
+                if let Some(v) = server { acc.target_server = Some(v); }
+                if let Some(v) = owner { acc.user_id = Some(v); }
+                if let Some(v) = status { acc.status = v; }
+                if let Some(v) = handout { acc.handout_enabled = v; }
+                if let Some(v) = ping { acc.ping_enabled = v; }
+                if let Some(v) = potion_slot { acc.potion_slot = Some(v); }
+                if let Some(v) = refill_count { acc.refill_count = Some(v); }
+                if let Some(v) = max_refills { acc.max_refills = u32::try_from(v).ok(); }
+                if let Some(v) = spend_on_events { acc.spend_on_events = Some(v); }
+                if let Some(v) = &next_mode {
+                    acc.next_mode = Some(if v == "manual" { NextMode::Manual } else { NextMode::Auto });
+                }
+            });
+            match report(edited) {
+                Ok(true) => {
+                    println!("[INFO] Updated account {}.", name);
+                    Ok(())
+                },
+                Ok(false) => {
+                    println!("[ERROR] No account named '{}'.", name);
+                    Err(EXIT_USAGE)
+                },
+                Err(code) => Err(code),
+            }
+        },
+        DbCommand::EncryptCodes => match db.upgrade_secrets() {
+            Err(e) => {
+                println!("[ERROR] {}", e);
+                Err(EXIT_SETUP)
+            },
+            Ok(upgraded) => {
+                if upgraded > 0 {
+                    if let Err(code) = report(db.save()) {
+                        return code;
+                    }
+                }
+                println!("[INFO] Re-encrypted {} stored secret(s) in the current format.", upgraded);
+                let secrets = db.check_secrets();
+                if secrets.is_ok() {
+                    println!(
+                        "[INFO] All {} restore code(s) and {} cookie(s) are encrypted with the current key.",
+                        db.data.accounts.len(),
+                        db.data.settings.session_cookies.len()
+                    );
+                    Ok(())
+                } else {
+                    println!("[ERROR] These secrets cannot be decrypted with the current ENCRYPTION_KEY and were left alone:\n{}", secrets.summary());
+                    Err(EXIT_SETUP)
+                }
+            },
+        },
+        DbCommand::ResetStatuses => report(db.reset_all_statuses())
+            .map(|_| println!("[INFO] Reset {} account(s) to pending.", db.data.accounts.len())),
+        DbCommand::SetCookie { label, value } => match secret_arg(value, "cookie value") {
+            Ok(value) => report(db.set_cookie(&label, &value, Some("cli".to_string())))
+                .map(|_| println!("[INFO] Saved session cookie '{}'.", label)),
+            Err(e) => {
+                println!("[ERROR] {}", e);
+                Err(EXIT_USAGE)
+            },
+        },
+        DbCommand::Admins => {
+            let settings = &db.data.settings;
+            println!("Owner (OWNER_ID): {}", std::env::var("OWNER_ID").unwrap_or_else(|_| "not set".to_string()));
+            println!("Admin role:       {}", settings.admin_role_id.as_deref().unwrap_or("not set"));
+            if settings.admins.is_empty() {
+                println!("Bot admins:       none");
+            } else {
+                println!("Bot admins:");
+                for admin in &settings.admins {
+                    println!("  {}", admin);
+                }
+            }
+            Ok(())
+        },
+        DbCommand::Validate { .. } | DbCommand::Merge { .. } => unreachable!("handled without loading the database"),
+    };
+    match result {
+        Ok(()) => EXIT_OK,
+        Err(code) => code,
+    }
+}
+
+fn add_account(db: &mut Database, name: String, code: Option<String>, server: Option<String>, owner: Option<String>, handout: bool) -> Result<(), i32> {
+    let code = secret_arg(code, "restore code").map_err(|e| {
+        println!("[ERROR] {}", e);
+        EXIT_USAGE
+    })?;
+    let encrypted = db.encrypt_secret(&code).map_err(|e| {
+        println!("[ERROR] Cannot encrypt the restore code: {}", e);
+        EXIT_SETUP
+    })?;
+    let replaced = db.data.accounts.iter().any(|a| a.name == name);
+    report(db.add_account(Account {
+        target_server: server,
+        user_id: owner,
+        handout_enabled: handout,
+        ..Account::new(&name, encrypted)
+    }))?;
+    println!("[INFO] {} account {}.", if replaced { "Replaced" } else { "Added" }, name);
+    Ok(())
+}
+
+fn validate(file: Option<String>) -> i32 {
+    let key = Cipher::from_env().ok();
+    let (backend, configured) = db::configured_backend();
+    let findings = match file {
+        Some(path) => validate_file(&path, key.as_ref()),
+        None if backend == "sqlite" => {
+            if !std::path::Path::new(&configured).exists() {
+                println!("[ERROR] SQLite database '{}' not found.", configured);
+                return EXIT_SETUP;
+            }
+            let loaded = SqliteStorage::open(&configured).and_then(|mut storage| storage.load());
+            match loaded {
+                Ok(data) => Ok(maintenance::validate_data(&data, key.as_ref())),
+                Err(e) => Err(e.to_string()),
+            }
+        },
+        None => validate_file(&configured, key.as_ref()),
+    };
+    let findings = match findings {
+        Ok(findings) => findings,
+        Err(e) => {
+            println!("[ERROR] {}", e);
+            return EXIT_SETUP;
+        },
+    };
+
+    for finding in &findings {
+        println!("{}", finding);
+    }
+    let errors = findings.iter().filter(|f| f.severity == Severity::Error).count();
+    println!("{} error(s), {} warning(s).", errors, findings.len() - errors);
+    if errors > 0 { EXIT_RUN_FAILED } else { EXIT_OK }
+}
+
+fn validate_file(path: &str, key: Option<&Cipher>) -> Result<Vec<maintenance::Finding>, String> {
+    let raw = std::fs::read_to_string(path).map_err(|e| format!("cannot read '{}': {}", path, e))?;
+    println!("Checking {}", path);
+    Ok(maintenance::validate_json(&raw, key))
+}
+
+fn read_db_file(path: &str) -> Result<DbData, i32> {
+    let raw = std::fs::read_to_string(path).map_err(|e| {
+        println!("[ERROR] Cannot read '{}': {}", path, e);
+        EXIT_SETUP
+    })?;
+    serde_json::from_str(&raw).map_err(|e| {
+        println!("[ERROR] '{}' does not match the database schema: {} (see `db validate {}`)", path, e, path);
+        EXIT_SETUP
+    })
+}
+
+fn merge(base_path: &str, other_path: &str, out: Option<&str>, other_key: Option<&str>, dry_run: bool) -> i32 {
+    let base_key = match Cipher::from_env() {
+        Ok(key) => key,
+        Err(e) => {
+            println!("[ERROR] {}", e);
+            return EXIT_SETUP;
+        },
+    };
+    let other_key_given = other_key.is_some();
+    let other_key = other_key.map(Cipher::new).unwrap_or_else(|| base_key.clone());
+    let (mut base, other) = match (read_db_file(base_path), read_db_file(other_path)) {
+        (Ok(base), Ok(other)) => (base, other),
+        (Err(code), _) | (_, Err(code)) => return code,
+    };
+
+    let report = maintenance::merge(&mut base, &other, &base_key, &other_key);
+    println!("Added {} account(s): {}", report.added.len(), report.added.join(", "));
+    println!("Updated {} account(s): {}", report.updated.len(), report.updated.join(", "));
+    println!("Added {} cookie(s), {} admin(s), {} history record(s).", report.cookies_added.len(), report.admins_added, report.history_added);
+    for conflict in &report.conflicts {
+        println!("[CONFLICT] {}", conflict);
+    }
+    for skipped in &report.skipped {
+        println!("[SKIPPED] {}", skipped);
+    }
+    if !report.skipped.is_empty() && !other_key_given {
+        println!("[HINT] If {} uses a different ENCRYPTION_KEY, pass it with --other-key.", other_path);
+    }
+
+    let Some(out) = out.filter(|_| !dry_run) else {
+        println!("Dry run, nothing written.");
+        return EXIT_OK;
+    };
+    match JsonStorage::new(out.to_string()).save_all(&base) {
+        Ok(()) => EXIT_OK,
+        Err(e) => {
+            println!("[ERROR] Failed to write {}: {}", out, e);
+            EXIT_SETUP
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+    use evertext_bot_rust::protocol::stand_in::{Script, StandIn};
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("evertext_bot_rust").chain(args.iter().copied()))
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("evertext-cli-{}-{}.json", name, std::process::id())).display().to_string()
+    }
+
+    #[test]
+    fn arguments_parse_into_commands() {
+        Cli::command().debug_assert();
+        assert!(parse(&[]).unwrap().command.is_none(), "no subcommand starts the bot");
+
+        let Some(Command::Run { account, all, mode }) = parse(&["run", "--all", "--mode", "handout"]).unwrap().command else {
+            panic!("expected run");
+        };
+        assert!(all && account.is_none() && matches!(mode, ModeArg::Handout));
+
+        let Some(Command::Db(DbCommand::EditAccount { max_refills, next_mode, .. })) =
+            parse(&["db", "edit-account", "A", "--max-refills", "-1", "--next-mode", "manual"]).unwrap().command
+        else {
+            panic!("expected db edit-account");
+        };
+        assert_eq!((max_refills, next_mode.as_deref()), (Some(-1), Some("manual")));
+        assert!(
+            matches!(parse(&["rotate-key"]).unwrap().command, Some(Command::RotateKey { old_key: None, new_key: None })),
+            "keys left out are read from stdin"
+        );
+    }
+
+    #[test]
+    fn bad_arguments_exit_with_the_usage_code() {
+        for args in [
+            &["run"][..],
+            &["run", "--account", "A", "--all"],
+            &["run", "--all", "--mode", "weekly"],
+            &["db", "edit-account", "A", "--potion-slot", "4"],
+            &["db", "merge", "a.json", "b.json"],
+            &["bogus"],
+        ] {
+            let err = parse(args).err().unwrap_or_else(|| panic!("{:?} should not parse", args));
+            assert_eq!(err.exit_code(), EXIT_USAGE, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn exit_codes_of_database_commands() {
+        assert_eq!(validate(Some(temp_path("missing"))), EXIT_SETUP);
+        let broken = temp_path("broken");
+        std::fs::write(&broken, "{").unwrap();
+        assert_eq!(validate(Some(broken.clone())), EXIT_RUN_FAILED);
+        assert_eq!(read_db_file(&broken).err(), Some(EXIT_SETUP));
+        let _ = std::fs::remove_file(broken);
+
+        let path = temp_path("admin");
+        std::fs::write(&path, r#"{"accounts": [{"name": "A", "code": "", "pingEnabled": false, "status": "done"}], "settings": {}}"#).unwrap();
+        let mut db = Database::open(Box::new(JsonStorage::new(path.clone())), LoadMode::CookieOptional).unwrap();
+        assert_eq!(admin(&mut db, DbCommand::RemoveAccount { name: "nobody".to_string() }), EXIT_USAGE);
+        assert_eq!(admin(&mut db, DbCommand::ResetStatuses), EXIT_OK);
+        assert_eq!(db.data.accounts[0].status, "pending");
+        let _ = std::fs::remove_file(path);
+    }
+
+    /// Plays account "A" once against a stand-in running `script` and returns the exit code
+    /// with the database as stored afterwards. `cancel_after_ms` plays the Ctrl-C.
+    async fn run_against(name: &str, script: Script, mode: RunMode, cancel_after_ms: Option<u64>) -> (i32, Vec<String>, Database) {
+        let server = StandIn::start(script).await;
+        let cipher = Cipher::new("test key");
+        let path = temp_path(name);
+        let data = serde_json::json!({
+            "accounts": [{"name": "A", "code": cipher.encrypt("RESTORE123"), "pingEnabled": false, "status": "pending", "handoutEnabled": true}],
+            "settings": {
+                "sessionCookies": [{"label": "default", "value": cipher.encrypt("cookie")}],
+                "client": {"wsUrl": server.ws_url, "httpUrl": server.http_url, "transcriptDir": ""}
+            }
+        });
+        std::fs::write(&path, data.to_string()).unwrap();
+        let open = || Database::open_with_cipher(Box::new(JsonStorage::new(path.clone())), LoadMode::Strict, Some(cipher.clone())).unwrap();
+
+        let mut db = open();
+        let targets = db.data.accounts.clone();
+        let cancel = CancellationToken::new();
+        if let Some(ms) = cancel_after_ms {
+            let trigger = cancel.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(std::time::Duration::from_millis(ms)).await;
+                trigger.cancel();
+            });
+        }
+        let code = run_targets(&mut db, targets, mode, &cancel).await;
+        let inputs = server.inputs().await;
+        let stored = open();
+        let _ = std::fs::remove_file(&path);
+        (code, inputs, stored)
+    }
+
+    #[tokio::test]
+    async fn run_outcomes_against_the_stand_in() {
+        let completes = Script::new()
+            .prompt("Enter Command to use")
+            .prompt("Enter Restore code")
+            .output("Press y to perform more commands:");
+        let (code, inputs, db) = run_against("done", completes, RunMode::Daily, None).await;
+        assert_eq!(code, EXIT_OK);
+        assert_eq!(inputs, ["d", "RESTORE123"]);
+        assert_eq!(db.data.accounts[0].status, "done");
+        assert_eq!(db.data.history.len(), 1);
+
+        let refused = Script::new()
+            .prompt("Enter Command to use")
+            .output("Access to start bot is restricted only for logged in users");
+        let (code, _, db) = run_against("login", refused, RunMode::Daily, None).await;
+        assert_eq!(code, EXIT_LOGIN_REQUIRED, "the only cookie is quarantined");
+        assert_eq!(db.healthy_cookies(), 0);
+        assert_eq!(db.data.accounts[0].status, "pending", "a refused cookie isn't the account's fault");
+        assert!(db.data.accounts[0].retry_counts.is_empty());
+
+        let stalls = || Script::new()
+            .prompt("Enter Command to use")
+            .prompt("Something the rules don't answer");
+        let (code, _, db) = run_against("cancel-daily", stalls(), RunMode::Daily, Some(1500)).await;
+        assert_eq!(code, EXIT_INTERRUPTED);
+        assert_eq!(db.data.accounts[0].status, "stopped");
+        let (code, _, db) = run_against("cancel-handout", stalls(), RunMode::Handout, Some(1500)).await;
+        assert_eq!(code, EXIT_INTERRUPTED);
+        assert_eq!(db.data.accounts[0].status, "pending", "a stopped handout leaves the daily alone");
+    }
+
+    #[tokio::test]
+    async fn run_exit_codes_without_sessions() {
+        let path = temp_path("run");
+        std::fs::write(&path, r#"{"accounts": [{"name": "A", "code": "", "pingEnabled": false, "status": "done"}], "settings": {}}"#).unwrap();
+        let mut db = Database::open(Box::new(JsonStorage::new(path.clone())), LoadMode::CookieOptional).unwrap();
+        assert_eq!(run_accounts(&mut db, Some("nobody"), RunMode::Daily).await, EXIT_USAGE);
+        assert_eq!(run_accounts(&mut db, None, RunMode::Daily).await, EXIT_OK, "finished accounts leave nothing to run");
+        let _ = std::fs::remove_file(path);
+    }
+}
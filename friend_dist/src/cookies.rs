@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::{Cipher, CryptoError};
+
+/// One EverText `session` cookie in the pool (`Settings.sessionCookies`).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SessionCookie {
+    pub label: String,
+    /// Encrypted with `ENCRYPTION_KEY` like restore codes; use `secret()` to read it.
+    pub value: String,
+    #[serde(rename = "addedBy", default)]
+    pub added_by: Option<String>,
+    #[serde(rename = "addedAt", default)]
+    pub added_at: Option<String>,
+    /// Last time a session or health check got past the login wall with it
+    #[serde(rename = "lastValidated", default)]
+    pub last_validated: Option<String>,
+    #[serde(rename = "lastUsed", default)]
+    pub last_used: Option<String>,
+    #[serde(rename = "failureCount", default)]
+    pub failure_count: u32,
+    /// Set when the game answered "Access to start bot is restricted"; skipped until restored
+    #[serde(default)]
+    pub quarantined: bool,
+}
+
+impl SessionCookie {
+    pub fn new(label: &str, value: &str, added_by: Option<String>) -> Self {
+        Self {
+            label: label.to_string(),
+            value: value.to_string(),
+            added_by,
+            added_at: Some(chrono::Utc::now().to_rfc3339()),
+            last_validated: None,
+            last_used: None,
+            failure_count: 0,
+            quarantined: false,
+        }
+    }
+
+    /// The decrypted cookie value.
+    pub fn secret(&self, cipher: &Cipher) -> Result<String, CryptoError> {
+        cipher.decrypt(&self.value)
+    }
+
+    /// First few characters only, for listings. `cipher` is None when no key is set.
+    pub fn masked(&self, cipher: Option<&Cipher>) -> String {
+        match cipher.ok_or(CryptoError::MissingKey).and_then(|c| self.secret(c)) {
+            Ok(value) => format!("{}…", value.chars().take(8).collect::<String>()),
+            Err(_) => "(undecryptable)".to_string(),
+        }
+    }
+}
+
+/// How the next cookie is chosen (`Settings.cookieSelection`).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CookieSelection {
+    /// Healthy cookies take turns in list order.
+    #[default]
+    RoundRobin,
+    /// The healthy cookie that has been idle the longest.
+    Lru,
+}
+
+/// Index of the cookie to use next, or None when every cookie is quarantined.
+pub fn select(cookies: &[SessionCookie], strategy: CookieSelection) -> Option<usize> {
+    let healthy = || cookies.iter().enumerate().filter(|(_, c)| !c.quarantined);
+    match strategy {
+        CookieSelection::Lru => healthy()
+            .min_by(|(_, a), (_, b)| a.last_used.cmp(&b.last_used))
+            .map(|(i, _)| i),
+        CookieSelection::RoundRobin => {
+            // Continue after the most recently used cookie, wrapping around
+            let last = cookies.iter().enumerate()
+                .filter(|(_, c)| c.last_used.is_some())
+                .max_by(|(_, a), (_, b)| a.last_used.cmp(&b.last_used))
+                .map(|(i, _)| i);
+            match last {
+                Some(last) => healthy().find(|(i, _)| *i > last).or_else(|| healthy().next()).map(|(i, _)| i),
+                None => healthy().next().map(|(i, _)| i),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool() -> Vec<SessionCookie> {
+        ["a", "b", "c"].iter().map(|l| SessionCookie::new(l, l, None)).collect()
+    }
+
+    fn use_next(cookies: &mut [SessionCookie], strategy: CookieSelection, tick: u32) -> Option<String> {
+        let i = select(cookies, strategy)?;
+        cookies[i].last_used = Some(format!("2024-01-01T00:00:{:02}Z", tick));
+        Some(cookies[i].label.clone())
+    }
+
+    #[test]
+    fn round_robin_skips_quarantined() {
+        let mut cookies = pool();
+        cookies[1].quarantined = true;
+        let picked: Vec<_> = (0..4).filter_map(|t| use_next(&mut cookies, CookieSelection::RoundRobin, t)).collect();
+        assert_eq!(picked, ["a", "c", "a", "c"]);
+    }
+
+    #[test]
+    fn lru_prefers_unused_then_oldest() {
+        let mut cookies = pool();
+        cookies[0].last_used = Some("2024-01-01T00:00:50Z".to_string());
+        let picked: Vec<_> = (0..3).filter_map(|t| use_next(&mut cookies, CookieSelection::Lru, t)).collect();
+        assert_eq!(picked, ["b", "c", "b"]);
+    }
+
+    #[test]
+    fn nothing_left_when_all_quarantined() {
+        let mut cookies = pool();
+        cookies.iter_mut().for_each(|c| c.quarantined = true);
+        assert_eq!(select(&cookies, CookieSelection::RoundRobin), None);
+        assert_eq!(select(&cookies, CookieSelection::Lru), None);
+    }
+}
@@ -0,0 +1,318 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use magic_crypt::MagicCryptTrait;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Prefix of the current ciphertext format:
+/// `v1:argon2id,m=<KiB>,t=<passes>,p=<lanes>:<base64 salt>:<base64(nonce || AES-256-GCM ciphertext and tag)>`.
+/// The AES key is derived from the passphrase with Argon2id under the stored parameters and salt.
+/// Values without a version prefix are either pre-v1 magic-crypt ciphertext or plaintext.
+pub const ENVELOPE_V1: &str = "v1:";
+
+const NONCE_LEN: usize = 12;
+const SALT_LEN: usize = 16;
+
+/// Argon2id cost for new ciphertext (the OWASP minimum: 19 MiB, 2 passes, 1 lane).
+const KDF_PARAMS: KdfParams = KdfParams { m_cost: 19 * 1024, t_cost: 2, p_cost: 1 };
+
+/// The placeholder older versions used when `ENCRYPTION_KEY` was unset. Never a real key.
+const INSECURE_DEFAULT_KEY: &str = "default_insecure_key";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CryptoError {
+    /// `ENCRYPTION_KEY` is not set.
+    MissingKey,
+    /// The value is authenticated ciphertext for a different key (or was tampered with).
+    WrongKey,
+    /// Pre-v1 ciphertext that doesn't decrypt under the current key.
+    LegacyUndecryptable,
+    /// The value isn't encrypted at all.
+    NotEncrypted,
+    Malformed(String),
+}
+
+impl std::fmt::Display for CryptoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CryptoError::MissingKey => write!(f, "ENCRYPTION_KEY is not set"),
+            CryptoError::WrongKey => write!(f, "encrypted with a different ENCRYPTION_KEY (or corrupted)"),
+            CryptoError::LegacyUndecryptable => write!(f, "old-format ciphertext that does not decrypt with the current ENCRYPTION_KEY"),
+            CryptoError::NotEncrypted => write!(f, "stored in plaintext"),
+            CryptoError::Malformed(why) => write!(f, "malformed ciphertext: {}", why),
+        }
+    }
+}
+
+impl std::error::Error for CryptoError {}
+
+/// Argon2id cost parameters, as stored in the envelope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct KdfParams {
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+impl KdfParams {
+    /// Parses `argon2id,m=..,t=..,p=..`. Costs far above `KDF_PARAMS` are refused so a
+    /// crafted value can't make the bot allocate gigabytes.
+    fn parse(text: &str) -> Result<Self, CryptoError> {
+        let malformed = || CryptoError::Malformed(format!("unknown key derivation '{}'", text));
+        let mut parts = text.split(',');
+        if parts.next() != Some("argon2id") {
+            return Err(malformed());
+        }
+        let mut cost = |name: &str, max: u32| {
+            parts.next()
+                .and_then(|p| p.strip_prefix(name))
+                .and_then(|v| v.parse::<u32>().ok())
+                .filter(|v| (1..=max).contains(v))
+                .ok_or_else(malformed)
+        };
+        let params = Self { m_cost: cost("m=", 256 * 1024)?, t_cost: cost("t=", 16)?, p_cost: cost("p=", 16)? };
+        if parts.next().is_some() {
+            return Err(malformed());
+        }
+        Ok(params)
+    }
+}
+
+impl std::fmt::Display for KdfParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "argon2id,m={},t={},p={}", self.m_cost, self.t_cost, self.p_cost)
+    }
+}
+
+/// AES keys already derived, by KDF parameters and salt.
+type KeyCache = HashMap<(KdfParams, Vec<u8>), Aes256Gcm>;
+
+/// Encrypts and decrypts stored secrets (restore codes, session cookies) under one passphrase.
+#[derive(Clone)]
+pub struct Cipher {
+    passphrase: String,
+    /// Salt for everything this cipher encrypts, so the KDF runs once per cipher.
+    salt: [u8; SALT_LEN],
+    /// Shared between clones.
+    keys: Arc<Mutex<KeyCache>>,
+}
+
+impl Cipher {
+    /// Keys are derived with Argon2id when first needed; new ciphertext gets a fresh random salt.
+    pub fn new(passphrase: &str) -> Self {
+        Self {
+            passphrase: passphrase.to_string(),
+            salt: rand::random(),
+            keys: Arc::default(),
+        }
+    }
+
+    /// Cipher for `ENCRYPTION_KEY`. Read once at startup; `Database` keeps the result.
+    pub fn from_env() -> Result<Self, CryptoError> {
+        match std::env::var("ENCRYPTION_KEY") {
+            Ok(key) if !key.is_empty() && key != INSECURE_DEFAULT_KEY => Ok(Self::new(&key)),
+            _ => Err(CryptoError::MissingKey),
+        }
+    }
+
+    pub fn encrypt(&self, plain: &str) -> String {
+        let aead = self.key(KDF_PARAMS, &self.salt).expect("the built-in KDF parameters are valid");
+        format!("{}{}:{}:{}", ENVELOPE_V1, KDF_PARAMS, STANDARD.encode(self.salt), seal(&aead, plain))
+    }
+
+    pub fn decrypt(&self, stored: &str) -> Result<String, CryptoError> {
+        self.decrypt_versioned(stored).map(|(plain, _)| plain)
+    }
+
+    /// Decrypts `stored` and tells whether it is in the current form (v1 with `KDF_PARAMS`).
+    fn decrypt_versioned(&self, stored: &str) -> Result<(String, bool), CryptoError> {
+        let Some(body) = stored.strip_prefix(ENVELOPE_V1) else {
+            return self.decrypt_legacy(stored).map(|plain| (plain, false));
+        };
+        let fields: Vec<&str> = body.split(':').collect();
+        match fields[..] {
+            [params, salt, payload] => {
+                let params = KdfParams::parse(params)?;
+                let salt = STANDARD.decode(salt).map_err(|e| CryptoError::Malformed(e.to_string()))?;
+                let plain = open(&self.key(params, &salt)?, payload)?;
+                Ok((plain, params == KDF_PARAMS))
+            },
+            _ => Err(CryptoError::Malformed("unexpected number of fields".to_string())),
+        }
+    }
+
+    /// The AES key for `params` and `salt`, derived once and then cached.
+    fn key(&self, params: KdfParams, salt: &[u8]) -> Result<Aes256Gcm, CryptoError> {
+        let mut keys = self.keys.lock().unwrap();
+        if let Some(aead) = keys.get(&(params, salt.to_vec())) {
+            return Ok(aead.clone());
+        }
+        let argon2 = Params::new(params.m_cost, params.t_cost, params.p_cost, Some(32))
+            .map(|p| Argon2::new(Algorithm::Argon2id, Version::V0x13, p))
+            .map_err(|e| CryptoError::Malformed(e.to_string()))?;
+        let mut key = [0u8; 32];
+        argon2.hash_password_into(self.passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| CryptoError::Malformed(e.to_string()))?;
+        let aead = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+        keys.insert((params, salt.to_vec()), aead.clone());
+        Ok(aead)
+    }
+
+    /// Unversioned values: magic-crypt ciphertext from before v1, or plaintext. Only values
+    /// that look like magic-crypt output are reported as undecryptable rather than taken for
+    /// plaintext: base64 of whole AES blocks that also carries padding or a `+`/`/`. Codes
+    /// and cookies are letters and digits, and magic-crypt output is padded for every value
+    /// shorter than 32 characters.
+    fn decrypt_legacy(&self, stored: &str) -> Result<String, CryptoError> {
+        let mc = magic_crypt::new_magic_crypt!(&self.passphrase, 256);
+        if let Ok(plain) = mc.decrypt_base64_to_string(stored) {
+            return Ok(plain);
+        }
+        if is_plaintext(stored) { Err(CryptoError::NotEncrypted) } else { Err(CryptoError::LegacyUndecryptable) }
+    }
+
+    /// The current form of `stored`, if it needs rewriting: legacy ciphertext and v1 under
+    /// older KDF parameters is re-wrapped and plaintext is encrypted. None when it already is
+    /// current ciphertext under this key.
+    pub fn upgrade(&self, stored: &str) -> Result<Option<String>, CryptoError> {
+        match self.decrypt_versioned(stored) {
+            Ok((_, true)) => Ok(None),
+            Ok((plain, false)) => Ok(Some(self.encrypt(&plain))),
+            Err(CryptoError::NotEncrypted) => Ok(Some(self.encrypt(stored))),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// The env file the bot loads at startup, relative to its working directory.
+pub const ENV_FILE: &str = ".env";
+
+/// Points `ENCRYPTION_KEY` in the env file at `path` (normally `ENV_FILE`) to `key`, keeping
+/// every other line. The file is replaced atomically, so a crash never leaves it half written.
+pub fn store_env_key(path: &std::path::Path, key: &str) -> std::io::Result<()> {
+    if key.contains(['\'', '\n', '\r']) {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "the key contains quotes or line breaks"));
+    }
+    let content = std::fs::read_to_string(path)?;
+    let line = format!("ENCRYPTION_KEY='{}'", key);
+    let mut replaced = false;
+    let mut lines: Vec<String> = content.lines()
+        .map(|l| if l.trim_start().starts_with("ENCRYPTION_KEY=") { replaced = true; line.clone() } else { l.to_string() })
+        .collect();
+    if !replaced {
+        lines.push(line);
+    }
+
+    let file_name = path.file_name().map_or(".env".into(), |n| n.to_string_lossy().into_owned());
+    let tmp = path.with_file_name(format!("{}.tmp", file_name));
+    std::fs::write(&tmp, lines.join("\n") + "\n")?;
+    std::fs::set_permissions(&tmp, std::fs::metadata(path)?.permissions())?;
+    std::fs::rename(&tmp, path)
+}
+
+/// True when `stored` is neither v1 ciphertext nor looks like magic-crypt output (see
+/// `Cipher::decrypt_legacy`). Needs no key, so plaintext can be reported even when
+/// `ENCRYPTION_KEY` is unset.
+pub fn is_plaintext(stored: &str) -> bool {
+    if stored.starts_with(ENVELOPE_V1) {
+        return false;
+    }
+    let marked = stored.ends_with('=') || stored.contains(['+', '/']);
+    !matches!(STANDARD.decode(stored), Ok(bytes) if marked && !bytes.is_empty() && bytes.len() % 16 == 0)
+}
+
+/// base64(nonce || AES-256-GCM ciphertext and tag) under a fresh nonce.
+fn seal(aead: &Aes256Gcm, plain: &str) -> String {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let sealed = aead.encrypt(&nonce, plain.as_bytes()).expect("AES-GCM encryption cannot fail for in-memory data");
+    let mut payload = nonce.to_vec();
+    payload.extend_from_slice(&sealed);
+    STANDARD.encode(payload)
+}
+
+/// Reverses `seal`. A failed authentication means the key is wrong.
+fn open(aead: &Aes256Gcm, payload: &str) -> Result<String, CryptoError> {
+    let payload = STANDARD.decode(payload).map_err(|e| CryptoError::Malformed(e.to_string()))?;
+    if payload.len() <= NONCE_LEN {
+        return Err(CryptoError::Malformed("too short".to_string()));
+    }
+    let (nonce, sealed) = payload.split_at(NONCE_LEN);
+    let plain = aead.decrypt(Nonce::from_slice(nonce), sealed).map_err(|_| CryptoError::WrongKey)?;
+    String::from_utf8(plain).map_err(|e| CryptoError::Malformed(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_and_wrong_key() {
+        let cipher = Cipher::new("correct horse");
+        let stored = cipher.encrypt("RESTORE123");
+        assert!(stored.starts_with("v1:argon2id,m=19456,t=2,p=1:"), "{}", stored);
+        assert_ne!(stored, cipher.encrypt("RESTORE123"), "nonces must differ");
+        assert_eq!(cipher.decrypt(&stored).unwrap(), "RESTORE123");
+        assert_eq!(Cipher::new("correct horse").decrypt(&stored).unwrap(), "RESTORE123", "salt travels with the value");
+        assert_eq!(Cipher::new("battery staple").decrypt(&stored), Err(CryptoError::WrongKey));
+
+        // Flip a character inside the nonce: authentication must fail
+        let at = stored.rfind(':').unwrap() + 2;
+        let flip = if stored.as_bytes()[at] == b'A' { "B" } else { "A" };
+        let tampered = format!("{}{}{}", &stored[..at], flip, &stored[at + 1..]);
+        assert_eq!(cipher.decrypt(&tampered), Err(CryptoError::WrongKey));
+    }
+
+    #[test]
+    fn kdf_parameters_are_checked() {
+        assert_eq!(KdfParams::parse("argon2id,m=19456,t=2,p=1"), Ok(KDF_PARAMS));
+        for bad in ["argon2i,m=19456,t=2,p=1", "argon2id,m=4194304,t=2,p=1", "argon2id,m=19456,t=0,p=1", "argon2id,m=19456,t=2"] {
+            assert!(matches!(KdfParams::parse(bad), Err(CryptoError::Malformed(_))), "{}", bad);
+        }
+        let cipher = Cipher::new("correct horse");
+        let stored = cipher.encrypt("RESTORE123").replacen("m=19456", "m=999999999", 1);
+        assert!(matches!(cipher.decrypt(&stored), Err(CryptoError::Malformed(_))));
+    }
+
+    #[test]
+    fn env_key_is_replaced_in_place() {
+        let path = std::env::temp_dir().join(format!("evertext-env-{}", std::process::id()));
+        std::fs::write(&path, "DISCORD_TOKEN=t\nENCRYPTION_KEY=old\nDATABASE_BACKEND=json\n").unwrap();
+        store_env_key(&path, "new key #1").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "DISCORD_TOKEN=t\nENCRYPTION_KEY='new key #1'\nDATABASE_BACKEND=json\n");
+
+        std::fs::write(&path, "DISCORD_TOKEN=t").unwrap();
+        store_env_key(&path, "second").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "DISCORD_TOKEN=t\nENCRYPTION_KEY='second'\n");
+        assert!(store_env_key(&path, "it's").is_err());
+        let _ = std::fs::remove_file(&path);
+        assert!(store_env_key(&path, "missing").is_err(), "no env file is created from nothing");
+    }
+
+    #[test]
+    fn upgrade_rewraps_legacy_and_plaintext() {
+        let cipher = Cipher::new("correct horse");
+        let legacy = magic_crypt::new_magic_crypt!("correct horse", 256).encrypt_str_to_base64("RESTORE123");
+        let upgraded = cipher.upgrade(&legacy).unwrap().expect("legacy value is rewritten");
+        assert_eq!(cipher.decrypt(&upgraded).unwrap(), "RESTORE123");
+        assert_eq!(cipher.upgrade(&upgraded), Ok(None));
+
+        assert_eq!(cipher.decrypt("RESTORE123"), Err(CryptoError::NotEncrypted));
+        let upgraded = cipher.upgrade("RESTORE123").unwrap().expect("plaintext is encrypted");
+        assert_eq!(cipher.decrypt(&upgraded).unwrap(), "RESTORE123");
+
+        // A letters-and-digits code that happens to be base64 of whole AES blocks is still plaintext
+        let code = "A1b2C3d4".repeat(8);
+        let upgraded = cipher.upgrade(&code).unwrap().expect("plaintext is encrypted");
+        assert_eq!(cipher.decrypt(&upgraded).unwrap(), code);
+        // ...but padded base64 of whole blocks is old ciphertext under some other key
+        assert_eq!(cipher.upgrade("MDEyMzQ1Njc4OWFiY2RlZg=="), Err(CryptoError::LegacyUndecryptable));
+
+        // v1 ciphertext for another key is never double-encrypted
+        let foreign = Cipher::new("battery staple").encrypt("RESTORE123");
+        assert_eq!(cipher.upgrade(&foreign), Err(CryptoError::WrongKey));
+    }
+}
@@ -1,6 +1,17 @@
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::collections::BTreeMap;
+use std::time::Duration;
 
+use crate::cookies::{self, CookieSelection, SessionCookie};
+use crate::crypto::{Cipher, CryptoError};
+use crate::protocol::config::ClientConfig;
+use crate::protocol::socket::{RunMode, SessionOutcome, SessionResult};
+use crate::retry::{RetryClass, RetryPolicy};
+use crate::storage::json::JsonStorage;
+use crate::storage::sqlite::SqliteStorage;
+use crate::storage::Storage;
+
+/// One game account. `code` is the restore code encrypted with `ENCRYPTION_KEY`.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Account {
     pub name: String,
@@ -19,12 +30,45 @@ pub struct Account {
     pub status: String,
     #[serde(rename = "lastRun")]
     pub last_run: Option<String>,
+    // --- Automation profile (unset = bot defaults) ---
+    #[serde(rename = "potionSlot", default)]
+    pub potion_slot: Option<u8>,
+    #[serde(rename = "refillCount", default)]
+    pub refill_count: Option<u32>,
+    #[serde(rename = "maxRefills", default)]
+    pub max_refills: Option<u32>,
+    #[serde(rename = "spendOnEvents", default)]
+    pub spend_on_events: Option<bool>,
+    #[serde(rename = "nextMode", default)]
+    pub next_mode: Option<NextMode>,
+    // Failed sessions this game day, per retry class (see retry.rs)
+    #[serde(rename = "retryCounts", default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub retry_counts: BTreeMap<String, u32>,
+    // Backoff after a failure: the daily queue skips the account until this time (RFC 3339)
+    #[serde(rename = "nextAttemptAt", default, skip_serializing_if = "Option::is_none")]
+    pub next_attempt_at: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// How to answer the first "next: Go to the next event" prompt.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NextMode {
+    /// Let the game auto-play the events.
+    Auto,
+    /// Step to the next event only.
+    Manual,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Settings {
-    #[serde(rename = "cookies")]
+    // Legacy single cookie; moved into `session_cookies` as "default" on load
+    #[serde(rename = "cookies", default, skip_serializing_if = "Option::is_none")]
     pub cookies: Option<String>,
+    // Pool of EverText session cookies the workers rotate through
+    #[serde(rename = "sessionCookies", default)]
+    pub session_cookies: Vec<SessionCookie>,
+    #[serde(rename = "cookieSelection", default)]
+    pub cookie_selection: CookieSelection,
     #[serde(rename = "adminRoleId")]
     pub admin_role_id: Option<String>,
     #[serde(rename = "logChannelId")]
@@ -37,166 +81,390 @@ pub struct Settings {
     // Scheduler state: Prevents double-trigger on restart at midnight
     #[serde(rename = "lastResetDate", default)]
     pub last_reset_date: Option<String>,
+    // Worker pool size: how many accounts may be in a session at once
+    #[serde(rename = "maxConcurrentSessions", default)]
+    pub max_concurrent_sessions: Option<usize>,
+    // Run history older than this many days is pruned
+    #[serde(rename = "historyRetentionDays", default)]
+    pub history_retention_days: Option<u32>,
+    // Endpoint / timeout overrides for the EverText client
+    #[serde(rename = "client", default)]
+    pub client: Option<ClientConfig>,
+    // Backoff and retry budget per failure class
+    #[serde(rename = "retryPolicy", default)]
+    pub retry_policy: Option<RetryPolicy>,
+    // Named cron jobs (see scheduler.rs)
+    #[serde(rename = "schedules", default, skip_serializing_if = "Vec::is_empty")]
+    pub schedules: Vec<JobSchedule>,
+}
+
+/// When one scheduler job runs.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JobSchedule {
+    pub name: String,
+    /// Crontab expression, 5 fields (`min hour dom mon dow`) or 6/7 with seconds
+    pub cron: String,
+    /// IANA timezone the expression is evaluated in, e.g. "Asia/Jakarta"
+    pub timezone: String,
+    pub enabled: bool,
+    /// Persisted so runs missed while the bot was down fire on the next start
+    #[serde(rename = "nextRun", default)]
+    pub next_run: Option<String>,
+}
+
+/// Number of snapshots `Database::backup` keeps.
+const BACKUPS_KEPT: usize = 14;
+
+/// Where backups go: `DATABASE_BACKUP_DIR`, default `backups`.
+pub fn backup_dir() -> String {
+    std::env::var("DATABASE_BACKUP_DIR").unwrap_or_else(|_| "backups".to_string())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// `(DATABASE_BACKEND, DATABASE_PATH)` with their defaults: `json` at `db.json`, or `db.sqlite` for `sqlite`.
+pub fn configured_backend() -> (String, String) {
+    let backend = std::env::var("DATABASE_BACKEND").unwrap_or_else(|_| "json".to_string());
+    let default_path = if backend == "sqlite" { "db.sqlite" } else { "db.json" };
+    let path = std::env::var("DATABASE_PATH").unwrap_or_else(|_| default_path.to_string());
+    (backend, path)
+}
+
+/// What `Database::rotate_key` re-encrypted.
+#[derive(Debug)]
+pub struct KeyRotation {
+    pub accounts: usize,
+    pub cookies: usize,
+    /// Snapshot of the database as it was before the rotation.
+    pub backup: String,
+}
+
+/// One finished (or failed) session of one account.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RunRecord {
+    pub account: String,
+    #[serde(rename = "startedAt")]
+    pub started_at: String,
+    #[serde(rename = "endedAt")]
+    pub ended_at: String,
+    /// "daily" or "handout"
+    pub mode: String,
+    /// "completed", a session error code (e.g. "ZIGZA_DETECTED") or "CONNECT_FAILED"
+    pub outcome: String,
+    /// How many earlier attempts this queue run already made for the account
+    pub retries: u32,
+    #[serde(rename = "finalPrompt")]
+    pub final_prompt: Option<String>,
+    /// Transcript file of the session (see `protocol::transcript`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transcript: Option<String>,
+}
+
+impl RunRecord {
+    /// History entry for a finished session. `None` means the connection never came up.
+    pub fn new(acc: &Account, mode: RunMode, started: chrono::DateTime<chrono::Utc>, retries: u32, result: Option<&SessionResult>) -> Self {
+        let (outcome, session) = match result {
+            Some(Ok(SessionOutcome::Completed(session))) => ("completed".to_string(), Some(session)),
+            Some(Err(e)) => (e.kind.code().to_string(), Some(&e.context)),
+            None => ("CONNECT_FAILED".to_string(), None),
+        };
+        Self {
+            account: acc.name.clone(),
+            started_at: started.to_rfc3339(),
+            ended_at: chrono::Utc::now().to_rfc3339(),
+            mode: mode.as_str().to_string(),
+            outcome,
+            retries,
+            final_prompt: session.and_then(|s| s.last_prompt.clone()),
+            transcript: session.and_then(|s| s.transcript.clone()),
+        }
+    }
+}
+
+pub const DEFAULT_HISTORY_RETENTION_DAYS: u32 = 30;
+
+/// Everything the database holds, as laid out in db.json.
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DbData {
     pub accounts: Vec<Account>,
     pub settings: Settings,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<RunRecord>,
 }
 
+/// The in-memory database plus its storage backend. Every mutating method saves.
 pub struct Database {
     pub data: DbData,
+    storage: Box<dyn Storage>,
+    /// Cipher for `ENCRYPTION_KEY`, read once when the database is opened. None when unset.
+    cipher: Option<Cipher>,
 }
 
-use magic_crypt::MagicCryptTrait;
-
 impl Account {
-    pub fn decrypt_code(&self) -> String {
-        let key = std::env::var("ENCRYPTION_KEY").unwrap_or_else(|_| "default_insecure_key".to_string());
-        if key == "default_insecure_key" {
-            // Warn only once or just proceed? For now, just return raw if likely not encrypted or using default
-             return self.code.clone();
-        }
-        let mc = magic_crypt::new_magic_crypt!(&key, 256);
-        match mc.decrypt_base64_to_string(&self.code) {
-             Ok(decrypted) => decrypted,
-             Err(_) => {
-                 // Fallback: maybe it's not encrypted yet? Return raw.
-                 self.code.clone()
-             }
+    /// A pending account with bot defaults. `code` must already be encrypted.
+    pub fn new(name: &str, code: String) -> Self {
+        Self {
+            name: name.to_string(),
+            code,
+            target_server: None,
+            user_id: None,
+            username: None,
+            discord_nickname: None,
+            ping_enabled: false,
+            handout_enabled: false,
+            status: "pending".to_string(),
+            last_run: None,
+            potion_slot: None,
+            refill_count: None,
+            max_refills: None,
+            spend_on_events: None,
+            next_mode: None,
+            retry_counts: Default::default(),
+            next_attempt_at: None,
         }
     }
 
-    pub fn encrypt_code_str(raw_code: &str) -> String {
-        let key = std::env::var("ENCRYPTION_KEY").unwrap_or_else(|_| "default_insecure_key".to_string());
-        if key == "default_insecure_key" {
-             return raw_code.to_string();
+    /// Still needs a successful daily run today: pending, or failed with retries left.
+    pub fn needs_daily_run(&self) -> bool {
+        self.status != "done" && self.status != "stopped" && (!self.status.starts_with("error") || self.status.contains("Retrying"))
+    }
+
+    /// When the account may be retried, if it is backing off.
+    pub fn retry_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        let at = chrono::DateTime::parse_from_rfc3339(self.next_attempt_at.as_deref()?).ok()?;
+        Some(at.with_timezone(&chrono::Utc))
+    }
+
+    pub fn decrypt_code(&self, cipher: &Cipher) -> Result<String, CryptoError> {
+        cipher.decrypt(&self.code)
+    }
+
+    pub fn potion(&self) -> u8 {
+        self.potion_slot.unwrap_or(3)
+    }
+
+    pub fn refills(&self) -> u32 {
+        self.refill_count.unwrap_or(1)
+    }
+
+    pub fn spends_on_events(&self) -> bool {
+        self.spend_on_events.unwrap_or(true)
+    }
+
+    pub fn next(&self) -> NextMode {
+        self.next_mode.unwrap_or(NextMode::Auto)
+    }
+}
+
+/// Stored secrets that don't decrypt under the current `ENCRYPTION_KEY`.
+#[derive(Debug, Default)]
+pub struct SecretCheck {
+    /// (account name, error)
+    pub accounts: Vec<(String, CryptoError)>,
+    /// (cookie label, error)
+    pub cookies: Vec<(String, CryptoError)>,
+}
+
+impl SecretCheck {
+    pub fn is_ok(&self) -> bool {
+        self.accounts.is_empty() && self.cookies.is_empty()
+    }
+
+    /// One line per failing secret, for logs and alerts.
+    pub fn summary(&self) -> String {
+        self.accounts.iter().map(|(name, e)| format!("• account **{}**: {}", name, e))
+            .chain(self.cookies.iter().map(|(label, e)| format!("• cookie `{}`: {}", label, e)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// How forgiving `Database::load` is about a missing database or session cookie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadMode {
+    /// The configured database must exist and hold at least one session cookie.
+    Strict,
+    /// The old behaviour: search other locations for db.json (restoring the embedded copy
+    /// in `embedded-db` builds) and start without a cookie. Opt in with `DATABASE_LEGACY_FALLBACK=true`.
+    Legacy,
+    /// The configured database must exist but may have no session cookie yet. For the
+    /// CLI commands that add one.
+    CookieOptional,
+}
+
+impl LoadMode {
+    pub fn from_env() -> Self {
+        match std::env::var("DATABASE_LEGACY_FALLBACK").map(|v| v.to_lowercase()) {
+            Ok(v) if v == "1" || v == "true" || v == "yes" => LoadMode::Legacy,
+            _ => LoadMode::Strict,
         }
-        let mc = magic_crypt::new_magic_crypt!(&key, 256);
-        mc.encrypt_str_to_base64(raw_code)
     }
 }
 
 impl Database {
+    /// Opens the backend selected by `DATABASE_BACKEND` (`json` by default, or `sqlite`)
+    /// at `DATABASE_PATH`, in the `LoadMode` given by the environment.
     pub fn load() -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let path = std::env::var("DATABASE_PATH").unwrap_or_else(|_| "db.json".to_string());
-        
-        // --- Diagnostics ---
-        if let Ok(cwd) = std::env::current_dir() {
-            println!("[DEBUG] Current working directory: {:?}", cwd);
-        }
-        for dir in [".", "/app", "/"] {
-            if let Ok(entries) = fs::read_dir(dir) {
-                let files: Vec<_> = entries.filter_map(|e| e.ok().map(|e| e.file_name().into_string().unwrap_or_default())).collect();
-                println!("[DEBUG] Files in '{}': {:?}", dir, files);
-            }
-        }
-        // --- End Diagnostics ---
+        Self::load_with(LoadMode::from_env())
+    }
 
-        let content = match fs::read_to_string(&path) {
-            Ok(c) => {
-                println!("[INFO] Loading database from file: {}", path);
-                c
+    /// `load` in an explicit `LoadMode`.
+    pub fn load_with(mode: LoadMode) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let (backend, path) = configured_backend();
+        let storage: Box<dyn Storage> = match backend.as_str() {
+            "json" => match mode {
+                LoadMode::Strict | LoadMode::CookieOptional => Box::new(JsonStorage::new(path)),
+                LoadMode::Legacy => Box::new(JsonStorage::with_legacy_fallback(path)),
             },
-            Err(_e) => {
-                println!("[WARN] Could not find database at {}. Searching fallbacks...", path);
-                // Try several fallback locations
-                let fallbacks = [
-                    "db.json", 
-                    "./db.json", 
-                    "/app/db.json", 
-                    "app/db.json", 
-                    "../db.json"
-                ];
-                let mut found_content = None;
-                
-                for fb in fallbacks {
-                    if let Ok(c) = fs::read_to_string(fb) {
-                        println!("[INFO] Found database at fallback: {}", fb);
-                        found_content = Some(c);
-                        break;
-                    }
+            "sqlite" => {
+                // SQLite would quietly create an empty database at a mistyped path
+                if mode != LoadMode::Legacy && !std::path::Path::new(&path).exists() {
+                    return Err(format!("SQLite database '{}' not found. Create it with `import-json`, or check DATABASE_PATH", path).into());
                 }
-                
-                match found_content {
-                    Some(c) => {
-                        println!("[INFO] Using database from fallback file.");
-                        c
-                    },
-                    None => {
-                        println!("[WARN] No database file found on disk. Using EMBEDDED database fallback.");
-                        let embedded = include_str!("../db.json").to_string();
-                        // AUTO-RESTORE: Write the embedded content to disk so we can save later
-                        let restore_path = "db.json";
-                        if let Err(e) = fs::write(restore_path, &embedded) {
-                            println!("[WARN] Failed to restore db.json to disk: {}", e);
-                        } else {
-                            println!("[INFO] successfully restored db.json from embedded backup to '{}'", restore_path);
-                        }
-                        embedded
-                    }
-                }
-            }
+                Box::new(SqliteStorage::open(&path)?)
+            },
+            other => return Err(format!("Unknown DATABASE_BACKEND '{}' (expected 'json' or 'sqlite')", other).into()),
         };
+        Self::open(storage, mode)
+    }
 
-        match serde_json::from_str::<DbData>(&content) {
-            Ok(mut data) => {
-                // AUTO-FIX: Inject cookie if missing (handles persistent DBs that are outdated)
-                if data.settings.cookies.is_none() || data.settings.cookies.as_deref() == Some("") {
-                    println!("[INFO] Database missing cookies. Injecting hardcoded fallback...");
-                    data.settings.cookies = Some(".eJw9kE1PwkAURf9L19bM53sz7MCSiLEQCAZxQ6Yzb0JFimmLZDT-dxtJ2N9z78n9yXaxpW6fjfr2THfZrg7ZKBPOMxcCGImBg9XCSM2FR14pHylUgXylIzlmLEgEDZJZFi0ox9FWnnvHMESUqHgQIISh6IXFYB0HJY2RMRquNQengjDeVgGF5SS0AWW9xmwQ-aT26Bpq-pvauaP26gfGACBKiUZpAcaygXDeU9ft-tOBmiFTrpdsUcxS-V6meTFOi2J7WRQvsrzcLz_eutI2doLfM_RRjpsJw1U9fV0fVdoPVS39n3Lr2m76qXyarfrmec3S4avcz-vObdLpUT4kPgDdsFyfmqudJhHBcJdHwVWuQoTcKRZztBDIABGBzH7_AJH3cHo.aUfevg.jxs6uzzbGWzu01-Fq_ecwOIFios".to_string());
-                }
-                Ok(Self { data })
-            },
+    /// Loads `storage` and migrates the legacy cookie field in memory. Nothing is written;
+    /// old-format secrets are re-encrypted by `upgrade_secrets`.
+    pub fn open(storage: Box<dyn Storage>, mode: LoadMode) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let cipher = match Cipher::from_env() {
+            Ok(cipher) => Some(cipher),
             Err(e) => {
-                println!("[ERROR] Failed to parse database JSON: {}", e);
-                // If parsing fails, we might as well return the error, 
-                // but at least we tried every path.
-                Err(e.into())
+                println!("[WARN] {}: restore codes and session cookies cannot be encrypted or decrypted.", e);
+                None
+            },
+        };
+        Self::open_with_cipher(storage, mode, cipher)
+    }
+
+    /// Like `open`, with the cipher given instead of read from `ENCRYPTION_KEY`.
+    pub fn open_with_cipher(mut storage: Box<dyn Storage>, mode: LoadMode, cipher: Option<Cipher>) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut data = storage.load()?;
+        if let Some(legacy) = data.settings.cookies.take().filter(|c| !c.is_empty()) {
+            if data.settings.session_cookies.is_empty() {
+                data.settings.session_cookies.push(SessionCookie::new("default", &legacy, None));
+            }
+        }
+        if data.settings.session_cookies.is_empty() {
+            match mode {
+                LoadMode::Strict => return Err(format!(
+                    "database {} has no session cookie. Put your EverText 'session' cookie in settings.cookies, or set DATABASE_LEGACY_FALLBACK=true to start without one and add it with /set_cookies",
+                    storage.describe()
+                ).into()),
+                LoadMode::Legacy => println!("[WARN] Database has no session cookie. Add one with /set_cookies."),
+                LoadMode::CookieOptional => {},
             }
         }
+        println!("[INFO] Database backend: {}", storage.describe());
+        Ok(Self { data, storage, cipher })
     }
 
-    pub fn save(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let path = std::env::var("DATABASE_PATH").unwrap_or_else(|_| "db.json".to_string());
-        let content = serde_json::to_string_pretty(&self.data)?;
-        
-        // Try to save to multiple locations to ensure persistence if possible
-        let paths = [path.as_str(), "db.json", "/app/db.json"];
-        let mut saved = false;
+    /// The cipher secrets are stored under.
+    pub fn cipher(&self) -> Result<&Cipher, CryptoError> {
+        self.cipher.as_ref().ok_or(CryptoError::MissingKey)
+    }
 
-        for p in paths {
-            if let Err(e) = fs::write(p, content.clone()) {
-                println!("[WARN] Failed to save database to {}: {}", p, e);
-            } else {
-                println!("[INFO] Successfully saved database to {}", p);
-                saved = true;
-                // We only need to save to one location successfully
-                break; // Added break here to stop trying once saved
+    /// Encrypts a secret (restore code, session cookie) for storage.
+    pub fn encrypt_secret(&self, raw: &str) -> Result<String, CryptoError> {
+        Ok(self.cipher()?.encrypt(raw))
+    }
+
+    pub fn decrypt_code(&self, acc: &Account) -> Result<String, CryptoError> {
+        acc.decrypt_code(self.cipher()?)
+    }
+
+    pub fn cookie_secret(&self, cookie: &SessionCookie) -> Result<String, CryptoError> {
+        cookie.secret(self.cipher()?)
+    }
+
+    /// Rewrites, in memory, every restore code and session cookie that isn't current
+    /// ciphertext under the database's cipher; call `save` to keep the result. Values that
+    /// don't decrypt are left alone for `check_secrets` to report. Returns how many changed.
+    pub fn upgrade_secrets(&mut self) -> Result<usize, CryptoError> {
+        let cipher = self.cipher()?.clone();
+        let mut accounts = 0;
+        for acc in self.data.accounts.iter_mut() {
+            if let Ok(Some(code)) = cipher.upgrade(&acc.code) {
+                acc.code = code;
+                accounts += 1;
+            }
+        }
+        let mut cookies = 0;
+        for cookie in self.data.settings.session_cookies.iter_mut() {
+            if let Ok(Some(value)) = cipher.upgrade(&cookie.value) {
+                cookie.value = value;
+                cookies += 1;
             }
         }
+        Ok(accounts + cookies)
+    }
 
-        if !saved {
-            println!("[ERROR] Failed to save database to ANY location!");
-            return Err("Failed to save database to any location".into());
+    /// Tries to decrypt every stored restore code and session cookie with the current key.
+    /// Without a key, plaintext values are still reported as such.
+    pub fn check_secrets(&self) -> SecretCheck {
+        let check = |stored: &str| match self.cipher() {
+            Ok(cipher) => cipher.decrypt(stored).err(),
+            Err(_) if crate::crypto::is_plaintext(stored) => Some(CryptoError::NotEncrypted),
+            Err(e) => Some(e),
+        };
+        SecretCheck {
+            accounts: self.data.accounts.iter()
+                .filter_map(|a| check(&a.code).map(|e| (a.name.clone(), e)))
+                .collect(),
+            cookies: self.data.settings.session_cookies.iter()
+                .filter_map(|c| check(&c.value).map(|e| (c.label.clone(), e)))
+                .collect(),
+        }
+    }
+
+    /// One-shot import of an existing db.json into a SQLite database.
+    pub fn import_json(json_path: &str, sqlite_path: &str) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let content = std::fs::read_to_string(json_path)?;
+        let data: DbData = serde_json::from_str(&content)?;
+        let mut target = SqliteStorage::open(sqlite_path)?;
+        target.save_all(&data)?;
+        Ok(data.accounts.len())
+    }
+
+    pub fn save(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.storage.save_all(&self.data)
+    }
+
+    pub fn save_settings(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.storage.save_settings(&self.data)
+    }
+
+    fn save_account(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        match self.data.accounts.iter().find(|a| a.name == name) {
+            Some(acc) => self.storage.save_accounts(&self.data, &[acc]),
+            None => Ok(()),
         }
-        Ok(())
     }
 
     pub fn update_status(&mut self, name: &str, status: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         if let Some(acc) = self.data.accounts.iter_mut().find(|a| a.name == name) {
             acc.status = status.to_string();
             acc.last_run = Some(chrono::Utc::now().to_rfc3339());
-            self.save()?;
+            // A finished run starts the next game day with a fresh retry budget
+            if status == "done" {
+                acc.retry_counts.clear();
+                acc.next_attempt_at = None;
+            }
+            self.save_account(name)?;
         }
         Ok(())
     }
 
+    /// Adds the account, or replaces the one with the same name in place (keeping its queue position).
     pub fn add_account(&mut self, account: Account) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        self.data.accounts.retain(|a| a.name != account.name);
-        self.data.accounts.push(account);
-        self.save()
+        let name = account.name.clone();
+        match self.data.accounts.iter_mut().find(|a| a.name == account.name) {
+            Some(existing) => *existing = account,
+            None => self.data.accounts.push(account),
+        }
+        self.save_account(&name)
     }
 
     pub fn remove_account(&mut self, name: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
@@ -204,7 +472,7 @@ impl Database {
         self.data.accounts.retain(|a| a.name != name);
         let found = self.data.accounts.len() < len_before;
         if found {
-            self.save()?;
+            self.storage.delete_account(&self.data, name)?;
         }
         Ok(found)
     }
@@ -212,10 +480,57 @@ impl Database {
     pub fn reset_all_statuses(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         for acc in self.data.accounts.iter_mut() {
             acc.status = "pending".to_string();
+            acc.retry_counts.clear();
+            acc.next_attempt_at = None;
         }
         self.save()
     }
 
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.data.settings.retry_policy.clone().unwrap_or_default()
+    }
+
+    /// Counts a failed session against the account's budget for `class` and updates its status.
+    /// Returns `(attempt, max_attempts, delay)` and sets `next_attempt_at` to now + delay.
+    /// `delay` is None once the budget is used up, in which case the account is parked
+    /// with a terminal error status until the next reset.
+    pub fn record_failure(&mut self, name: &str, class: RetryClass, code: &str) -> Result<(u32, u32, Option<Duration>), Box<dyn std::error::Error + Send + Sync>> {
+        let backoff = self.retry_policy().backoff(class).clone();
+        let Some(acc) = self.data.accounts.iter_mut().find(|a| a.name == name) else {
+            return Ok((0, backoff.max_attempts, None));
+        };
+
+        let attempt = {
+            let count = acc.retry_counts.entry(class.key().to_string()).or_insert(0);
+            *count += 1;
+            *count
+        };
+        let delay = (attempt < backoff.max_attempts).then(|| backoff.delay(attempt));
+        acc.status = match delay {
+            Some(_) => format!("error: {} Retrying ({}/{})", code, attempt, backoff.max_attempts),
+            None => format!("error: {} (gave up after {} attempts)", code, attempt),
+        };
+        let now = chrono::Utc::now();
+        acc.last_run = Some(now.to_rfc3339());
+        acc.next_attempt_at = delay
+            .and_then(|d| chrono::Duration::from_std(d).ok())
+            .map(|d| (now + d).to_rfc3339());
+        self.save_account(name)?;
+        Ok((attempt, backoff.max_attempts, delay))
+    }
+
+    /// Applies `edit` to the named account and saves it. Returns false if no such account.
+    pub fn edit_account<F: FnOnce(&mut Account)>(&mut self, name: &str, edit: F) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        match self.data.accounts.iter_mut().find(|a| a.name == name) {
+            Some(acc) => {
+                edit(acc);
+                self.save_account(name)?;
+                Ok(true)
+            },
+            None => Ok(false),
+        }
+    }
+
     pub fn toggle_ping(&mut self, user_id: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
         let mut new_state = false;
         let mut first = true;
@@ -236,23 +551,137 @@ impl Database {
                 acc.ping_enabled = new_state;
             }
         }
-        self.save()?;
+        let changed: Vec<&Account> = self.data.accounts.iter()
+            .filter(|a| a.user_id.as_deref() == Some(user_id))
+            .collect();
+        self.storage.save_accounts(&self.data, &changed)?;
         Ok(new_state)
     }
 
     pub fn set_mute(&mut self, mute: bool) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         self.data.settings.mute_bot_messages = Some(mute);
-        self.save()
+        self.save_settings()
     }
 
     pub fn set_log_channel(&mut self, channel_id: String) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         self.data.settings.log_channel_id = Some(channel_id);
-        self.save()
+        self.save_settings()
+    }
+
+    // --- SESSION COOKIE POOL ---
+    /// Picks the next healthy cookie (see `Settings.cookieSelection`) and marks it used.
+    /// Returns `(label, value)`, or None when every cookie is quarantined. A cookie that
+    /// doesn't decrypt under the current key is quarantined and skipped.
+    pub fn pick_cookie(&mut self) -> Option<(String, String)> {
+        let cipher = self.cipher().cloned();
+        loop {
+            let i = cookies::select(&self.data.settings.session_cookies, self.data.settings.cookie_selection)?;
+            let cookie = &mut self.data.settings.session_cookies[i];
+            match cipher.clone().and_then(|c| cookie.secret(&c)) {
+                Ok(value) => {
+                    cookie.last_used = Some(chrono::Utc::now().to_rfc3339());
+                    let picked = (cookie.label.clone(), value);
+                    if let Err(e) = self.save_settings() {
+                        println!("[WARN] Failed to save cookie usage: {}", e);
+                    }
+                    return Some(picked);
+                },
+                Err(e) => {
+                    println!("[ERROR] Session cookie '{}' cannot be decrypted ({}). Quarantining it.", cookie.label, e);
+                    cookie.quarantined = true;
+                    cookie.failure_count += 1;
+                    let _ = self.save_settings();
+                },
+            }
+        }
+    }
+
+    pub fn healthy_cookies(&self) -> usize {
+        self.data.settings.session_cookies.iter().filter(|c| !c.quarantined).count()
+    }
+
+    /// Adds a cookie, or replaces the value of the one with the same label (un-quarantining it).
+    /// The value is stored encrypted.
+    pub fn set_cookie(&mut self, label: &str, value: &str, added_by: Option<String>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let value = &self.encrypt_secret(value)?;
+        let pool = &mut self.data.settings.session_cookies;
+        match pool.iter_mut().find(|c| c.label == label) {
+            Some(cookie) => *cookie = SessionCookie::new(label, value, added_by),
+            None => pool.push(SessionCookie::new(label, value, added_by)),
+        }
+        self.save_settings()
+    }
+
+    pub fn remove_cookie(&mut self, label: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let before = self.data.settings.session_cookies.len();
+        self.data.settings.session_cookies.retain(|c| c.label != label);
+        let removed = self.data.settings.session_cookies.len() != before;
+        if removed {
+            self.save_settings()?;
+        }
+        Ok(removed)
+    }
+
+    /// Takes a cookie out of rotation after the game refused it. Returns false if unknown.
+    pub fn quarantine_cookie(&mut self, label: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(cookie) = self.data.settings.session_cookies.iter_mut().find(|c| c.label == label) else {
+            return Ok(false);
+        };
+        cookie.quarantined = true;
+        cookie.failure_count += 1;
+        self.save_settings()?;
+        Ok(true)
+    }
+
+    /// Records that the cookie got past the login wall, restoring it if it was quarantined.
+    /// Returns true if it was restored.
+    pub fn cookie_validated(&mut self, label: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(cookie) = self.data.settings.session_cookies.iter_mut().find(|c| c.label == label) else {
+            return Ok(false);
+        };
+        let restored = std::mem::take(&mut cookie.quarantined);
+        cookie.last_validated = Some(chrono::Utc::now().to_rfc3339());
+        cookie.failure_count = 0;
+        self.save_settings()?;
+        Ok(restored)
+    }
+
+    /// Stores the session cookie the server rotated `label` to (encrypted).
+    /// Returns false if the label is unknown or the value didn't change.
+    pub fn rotate_cookie(&mut self, label: &str, value: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let cipher = self.cipher()?.clone();
+        let Some(cookie) = self.data.settings.session_cookies.iter_mut().find(|c| c.label == label) else {
+            return Ok(false);
+        };
+        if cookie.secret(&cipher).is_ok_and(|current| current == value) {
+            return Ok(false);
+        }
+        cookie.value = cipher.encrypt(value);
+        self.save_settings()?;
+        Ok(true)
+    }
+
+    /// Counts a health check that could not reach a verdict, without quarantining.
+    pub fn cookie_check_failed(&mut self, label: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(cookie) = self.data.settings.session_cookies.iter_mut().find(|c| c.label == label) {
+            cookie.failure_count += 1;
+            self.save_settings()?;
+        }
+        Ok(())
+    }
+
+    pub fn set_concurrency(&mut self, limit: usize) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.data.settings.max_concurrent_sessions = Some(limit);
+        self.save_settings()
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.data.settings.max_concurrent_sessions.unwrap_or(crate::pool::DEFAULT_CONCURRENCY).max(1)
     }
 
     pub fn set_admin_role(&mut self, role_id: String) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         self.data.settings.admin_role_id = Some(role_id);
-        self.save()
+        self.save_settings()
     }
 
     pub fn get_user_accounts(&self, user_id: &str) -> Vec<Account> {
@@ -266,7 +695,7 @@ impl Database {
         if let Some(acc) = self.data.accounts.iter_mut().find(|a| a.name == name) {
             acc.handout_enabled = !acc.handout_enabled;
             let new_state = acc.handout_enabled;
-            self.save()?;
+            self.save_account(name)?;
             Ok(new_state)
         } else {
             Err("Account not found".into())
@@ -280,6 +709,114 @@ impl Database {
             .collect()
     }
 
+    /// Writes a JSON snapshot of the whole database to `dir` (default `backups`)
+    /// and keeps only the newest `BACKUPS_KEPT` snapshots.
+    pub fn backup(&self, dir: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let path = self.snapshot(dir, "db")?;
+
+        let mut snapshots: Vec<_> = std::fs::read_dir(dir)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("db-") && n.ends_with(".json")))
+            .collect();
+        snapshots.sort();
+        if snapshots.len() > BACKUPS_KEPT {
+            for old in &snapshots[..snapshots.len() - BACKUPS_KEPT] {
+                let _ = std::fs::remove_file(old);
+            }
+        }
+        Ok(path)
+    }
+
+    /// Writes `<dir>/<prefix>-<timestamp>.json`.
+    fn snapshot(&self, dir: &str, prefix: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        std::fs::create_dir_all(dir)?;
+        let path = std::path::Path::new(dir).join(format!("{}-{}.json", prefix, chrono::Utc::now().format("%Y%m%dT%H%M%SZ")));
+        std::fs::write(&path, serde_json::to_string_pretty(&self.data)?)?;
+        Ok(path.to_string_lossy().into_owned())
+    }
+
+    /// Re-encrypts every restore code and session cookie from `old` to `new`. Nothing is
+    /// changed unless every secret decrypts under `old` and round-trips under `new`; the
+    /// database is snapshotted to `backup_dir` (as `pre-rotation-*.json`, never pruned)
+    /// first, and written back in one save. On success `new` becomes the database's cipher.
+    pub fn rotate_key(&mut self, old: &Cipher, new: &Cipher, backup_dir: &str) -> Result<KeyRotation, Box<dyn std::error::Error + Send + Sync>> {
+        let reencrypt = |stored: &str| -> Result<String, String> {
+            let plain = match old.decrypt(stored) {
+                Ok(plain) => plain,
+                // Left over from before encryption; it gets encrypted now
+                Err(CryptoError::NotEncrypted) => stored.to_string(),
+                Err(e) => return Err(e.to_string()),
+            };
+            let rotated = new.encrypt(&plain);
+            match new.decrypt(&rotated) {
+                Ok(check) if check == plain => Ok(rotated),
+                _ => Err("does not round-trip under the new key".to_string()),
+            }
+        };
+
+        let mut data = self.data.clone();
+        let mut failures = Vec::new();
+        for acc in data.accounts.iter_mut() {
+            match reencrypt(&acc.code) {
+                Ok(code) => acc.code = code,
+                Err(e) => failures.push(format!("account {}: {}", acc.name, e)),
+            }
+        }
+        for cookie in data.settings.session_cookies.iter_mut() {
+            match reencrypt(&cookie.value) {
+                Ok(value) => cookie.value = value,
+                Err(e) => failures.push(format!("cookie {}: {}", cookie.label, e)),
+            }
+        }
+        if !failures.is_empty() {
+            return Err(format!("key rotation aborted, nothing was changed:\n{}", failures.join("\n")).into());
+        }
+
+        let backup = self.snapshot(backup_dir, "pre-rotation")?;
+        let previous = std::mem::replace(&mut self.data, data);
+        if let Err(e) = self.save() {
+            self.data = previous;
+            return Err(format!("key rotation aborted, saving failed: {} (backup: {})", e, backup).into());
+        }
+        // From here on everything is encrypted and decrypted under the new key
+        self.cipher = Some(new.clone());
+        Ok(KeyRotation {
+            accounts: self.data.accounts.len(),
+            cookies: self.data.settings.session_cookies.len(),
+            backup,
+        })
+    }
+
+    // --- RUN HISTORY ---
+    /// Appends a history entry and drops entries (and their transcripts) past the retention window.
+    pub fn record_run(&mut self, record: RunRecord) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.data.history.push(record);
+        let record = self.data.history.last().expect("just pushed");
+        self.storage.append_history(&self.data, record)?;
+
+        let days = self.data.settings.history_retention_days.unwrap_or(DEFAULT_HISTORY_RETENTION_DAYS);
+        let cutoff = (chrono::Utc::now() - chrono::Duration::days(days as i64)).to_rfc3339();
+        if self.data.history.iter().any(|r| r.started_at < cutoff) {
+            for path in self.data.history.iter().filter(|r| r.started_at < cutoff).filter_map(|r| r.transcript.as_ref()) {
+                let _ = std::fs::remove_file(path);
+            }
+            self.data.history.retain(|r| r.started_at >= cutoff);
+            self.storage.prune_history(&self.data, &cutoff)?;
+        }
+        Ok(())
+    }
+
+    /// History for one account from the last `days` days, newest first.
+    pub fn get_history(&self, name: &str, days: u32) -> Vec<RunRecord> {
+        let cutoff = (chrono::Utc::now() - chrono::Duration::days(days as i64)).to_rfc3339();
+        self.data.history.iter()
+            .rev()
+            .filter(|r| r.account == name && r.started_at >= cutoff)
+            .cloned()
+            .collect()
+    }
+
     // --- NEW ADMIN FUNCTIONS ---
     pub fn is_admin(&self, user_id: &str) -> bool {
         self.data.settings.admins.contains(&user_id.to_string())
@@ -288,7 +825,7 @@ impl Database {
     pub fn add_admin(&mut self, user_id: String) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
         if !self.data.settings.admins.contains(&user_id) {
             self.data.settings.admins.push(user_id);
-            self.save()?;
+            self.save_settings()?;
             Ok(true)
         } else {
             Ok(false)
@@ -298,7 +835,7 @@ impl Database {
     pub fn remove_admin(&mut self, user_id: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
         if let Some(pos) = self.data.settings.admins.iter().position(|x| x == user_id) {
             self.data.settings.admins.remove(pos);
-            self.save()?;
+            self.save_settings()?;
             Ok(true)
         } else {
             Ok(false)
@@ -309,3 +846,159 @@ impl Database {
         self.data.settings.admins.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("evertext-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn open_with(dir: &std::path::Path, code: &str, cookie: &str) -> Database {
+        let path = dir.join("db.json").display().to_string();
+        let data = serde_json::json!({
+            "accounts": [{"name": "A", "code": code, "pingEnabled": false, "status": "pending"}],
+            "settings": {"sessionCookies": [{"label": "default", "value": cookie}]}
+        });
+        std::fs::write(&path, data.to_string()).unwrap();
+        Database::open(Box::new(JsonStorage::new(path)), LoadMode::Strict).unwrap()
+    }
+
+    #[test]
+    fn secret_check_summary_lists_every_failure() {
+        let clean = SecretCheck { accounts: Vec::new(), cookies: Vec::new() };
+        assert!(clean.is_ok());
+        assert_eq!(clean.summary(), "");
+
+        let check = SecretCheck {
+            accounts: vec![("A".to_string(), CryptoError::WrongKey), ("B".to_string(), CryptoError::NotEncrypted)],
+            cookies: vec![("default".to_string(), CryptoError::LegacyUndecryptable)],
+        };
+        assert!(!check.is_ok());
+        assert_eq!(check.summary(), format!(
+            "• account **A**: {}\n• account **B**: stored in plaintext\n• cookie `default`: {}",
+            CryptoError::WrongKey, CryptoError::LegacyUndecryptable,
+        ));
+    }
+
+    #[test]
+    fn secret_check_reports_plaintext_without_a_key() {
+        let dir = temp_dir("check-nokey");
+        let mut db = open_with(&dir, "RESTORE123", &Cipher::new("k").encrypt("cookie"));
+        db.cipher = None;
+        let check = db.check_secrets();
+        assert_eq!(check.accounts, [("A".to_string(), CryptoError::NotEncrypted)]);
+        assert_eq!(check.cookies, [("default".to_string(), CryptoError::MissingKey)]);
+        assert_eq!(db.upgrade_secrets(), Err(CryptoError::MissingKey));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn key_rotation_reencrypts_and_keeps_a_backup() {
+        let dir = temp_dir("rotate");
+        let (old, new) = (Cipher::new("old key"), Cipher::new("new key"));
+        let mut db = open_with(&dir, &old.encrypt("RESTORE123"), &old.encrypt("cookie"));
+
+        let backups = dir.join("backups").display().to_string();
+        let rotation = db.rotate_key(&old, &new, &backups).expect("rotation");
+        assert_eq!((rotation.accounts, rotation.cookies), (1, 1));
+        assert_eq!(new.decrypt(&db.data.accounts[0].code).unwrap(), "RESTORE123");
+        assert_eq!(new.decrypt(&db.data.settings.session_cookies[0].value).unwrap(), "cookie");
+        // The database switches to the new key itself
+        assert!(db.check_secrets().is_ok());
+        assert_eq!(db.decrypt_code(&db.data.accounts[0]).unwrap(), "RESTORE123");
+
+        let before: DbData = serde_json::from_str(&std::fs::read_to_string(&rotation.backup).unwrap()).unwrap();
+        assert_eq!(old.decrypt(&before.accounts[0].code).unwrap(), "RESTORE123");
+        let saved = std::fs::read_to_string(dir.join("db.json")).unwrap();
+        assert!(saved.contains(&db.data.accounts[0].code));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn key_rotation_with_wrong_old_key_changes_nothing() {
+        let dir = temp_dir("rotate-wrong");
+        let stored = Cipher::new("real key").encrypt("RESTORE123");
+        let mut db = open_with(&dir, &stored, &Cipher::new("real key").encrypt("cookie"));
+
+        let err = db.rotate_key(&Cipher::new("guess"), &Cipher::new("new key"), &dir.join("backups").display().to_string())
+            .expect_err("wrong old key must abort");
+        assert!(err.to_string().contains("account A"), "{}", err);
+        assert_eq!(db.data.accounts[0].code, stored);
+        assert!(!dir.join("backups").exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    fn sample_data() -> DbData {
+        serde_json::from_value(serde_json::json!({
+            "accounts": [
+                {"name": "A", "code": "a", "pingEnabled": false, "status": "pending"},
+                {"name": "B", "code": "b", "pingEnabled": false, "status": "done", "targetServer": "E-1"}
+            ],
+            "settings": {"sessionCookies": [{"label": "default", "value": "c"}], "admins": ["1"]},
+            "history": [{"account": "B", "startedAt": "2024-01-01T00:00:00Z", "endedAt": "2024-01-01T00:05:00Z",
+                         "mode": "daily", "outcome": "completed", "retries": 0, "finalPrompt": null}]
+        })).unwrap()
+    }
+
+    fn names(db: &Database) -> Vec<&str> {
+        db.data.accounts.iter().map(|a| a.name.as_str()).collect()
+    }
+
+    #[test]
+    fn both_backends_round_trip_and_keep_account_order() {
+        let dir = temp_dir("backends");
+        let json = dir.join("db.json").display().to_string();
+        let sqlite = dir.join("db.sqlite").display().to_string();
+        JsonStorage::new(json.clone()).save_all(&sample_data()).unwrap();
+        SqliteStorage::open(&sqlite).unwrap().save_all(&sample_data()).unwrap();
+
+        let open = |path: &str| -> Box<dyn Storage> {
+            if path.ends_with(".json") { Box::new(JsonStorage::new(path.to_string())) } else { Box::new(SqliteStorage::open(path).unwrap()) }
+        };
+        for path in [&json, &sqlite] {
+            let mut db = Database::open(open(path), LoadMode::Legacy).unwrap();
+            db.add_account(Account::new("C", "c".to_string())).unwrap();
+            db.add_account(Account { status: "error: X".to_string(), ..Account::new("A", "a2".to_string()) }).unwrap();
+            db.remove_account("B").unwrap();
+            db.set_admin_role("role".to_string()).unwrap();
+            assert_eq!(names(&db), ["A", "C"]);
+
+            let db = Database::open(open(path), LoadMode::Legacy).unwrap();
+            assert_eq!(names(&db), ["A", "C"], "{}: a replaced account keeps its place", path);
+            assert_eq!(db.data.accounts[0].status, "error: X");
+            assert_eq!(db.data.settings.admin_role_id.as_deref(), Some("role"));
+            assert_eq!(db.data.settings.admins, ["1"]);
+            assert_eq!(db.data.history.len(), 1);
+        }
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn import_json_copies_accounts_settings_and_history() {
+        let dir = temp_dir("import");
+        let json = dir.join("db.json").display().to_string();
+        let sqlite = dir.join("db.sqlite").display().to_string();
+        std::fs::write(&json, serde_json::to_string(&sample_data()).unwrap()).unwrap();
+
+        assert_eq!(Database::import_json(&json, &sqlite).unwrap(), 2);
+        let data = SqliteStorage::open(&sqlite).unwrap().load().unwrap();
+        let expected = sample_data();
+        assert_eq!(serde_json::to_value(&data).unwrap(), serde_json::to_value(&expected).unwrap());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn needs_daily_run_skips_finished_and_given_up() {
+        let with = |status: &str| Account { status: status.to_string(), ..Account::new("A", String::new()) };
+        assert!(with("pending").needs_daily_run());
+        assert!(with("error: ZIGZA_DETECTED Retrying (1/3)").needs_daily_run());
+        assert!(!with("done").needs_daily_run());
+        assert!(!with("stopped").needs_daily_run());
+        assert!(!with("error: SERVER_FULL (gave up after 3 attempts)").needs_daily_run());
+    }
+}
@@ -0,0 +1,44 @@
+//! EverText automation: the Socket.IO game client (`protocol`), the account database (`db`)
+//! and the pieces the Discord bot binary is built from (cookie pool, retry policy, scheduler,
+//! worker pool, queue managers and embeds in `queue` and `bot`).
+//!
+//! Playing one account's daily run:
+//!
+//! ```no_run
+//! use evertext_bot_rust::{CancellationToken, ClientConfig, Database, EvertextClient, RunMode, SessionOutcome};
+//!
+//! # async fn daily() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+//! // DATABASE_BACKEND / DATABASE_PATH / ENCRYPTION_KEY as for the bot
+//! let mut db = Database::load()?;
+//! let account = db.data.accounts.iter().find(|a| a.name == "Geats").cloned().ok_or("no such account")?;
+//! let code = db.decrypt_code(&account)?;
+//! let (_label, cookie) = db.pick_cookie().ok_or("no healthy session cookie")?;
+//!
+//! let config = ClientConfig::resolve(db.data.settings.client.as_ref());
+//! let mut client = EvertextClient::connect(&config, &cookie).await?;
+//! match client.run_loop(&account, &code, RunMode::Daily, &CancellationToken::new()).await {
+//!     Ok(SessionOutcome::Completed(session)) => println!("done in {:?}", session.elapsed),
+//!     Err(e) => println!("failed: {}", e),
+//! }
+//! db.update_status(&account.name, "done")?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod bot;
+pub mod cookies;
+pub mod crypto;
+pub mod db;
+pub mod maintenance;
+pub mod pool;
+pub mod protocol;
+pub mod queue;
+pub mod retry;
+pub mod scheduler;
+pub mod storage;
+
+pub use db::{Account, Database};
+pub use protocol::config::ClientConfig;
+pub use protocol::socket::{EvertextClient, RunMode, SessionError, SessionErrorKind, SessionOutcome, SessionResult};
+/// Passed to `EvertextClient::run_loop` to stop a session.
+pub use tokio_util::sync::CancellationToken;
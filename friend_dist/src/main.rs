@@ -1,19 +1,33 @@
-mod protocol;
-mod db;
+mod cli;
 
-use protocol::socket::{EvertextClient, RunMode};
-use db::{Database, Account};
+use evertext_bot_rust::{crypto, db, pool, protocol, scheduler};
+use evertext_bot_rust::bot::{self, embeds, runner};
+use evertext_bot_rust::queue::{QueueKind, QueueSnapshot};
 
+use protocol::socket::RunMode;
+use db::{Database, Account, NextMode};
+use crypto::Cipher;
+use pool::WorkerPool;
+use scheduler::Job;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use serenity::all::*;
 use serenity::async_trait;
-use chrono::{Utc, Timelike};
+use clap::Parser;
+use chrono::Utc;
 // use chrono_tz::Asia::Jakarta; // Removed
 
+/// Discord rejects attachments over 8 MB on unboosted servers.
+const MAX_TRANSCRIPT_UPLOAD: usize = 8 * 1024 * 1024;
+
 struct Handler {
     db: Arc<Mutex<Database>>,
-    is_processing: Arc<Mutex<bool>>,
+    pool: Arc<WorkerPool>,
+    /// `ready` fires again on every gateway reconnect; the scheduler starts only once.
+    scheduler_started: AtomicBool,
 }
 
 impl Handler {
@@ -45,269 +59,12 @@ impl Handler {
         false
     }
 
-    async fn log_message(db: Arc<Mutex<Database>>, http: Arc<Http>, message: String, skip_channel: Option<ChannelId>) {
-        let db = db.lock().await;
-        if let Some(true) = db.data.settings.mute_bot_messages {
-            return;
-        }
-        if let Some(channel_id_str) = &db.data.settings.log_channel_id {
-            if let Ok(channel_id) = channel_id_str.parse::<u64>() {
-                let channel = ChannelId::new(channel_id);
-                if Some(channel) == skip_channel {
-                    return;
-                }
-                let _ = channel.say(&http, message).await;
-            }
-        }
-    }
-
-    async fn process_queue(&self, ctx: Context, user_id_filter: Option<String>, source_channel: Option<ChannelId>) {
-        let db_clone = Arc::clone(&self.db);
-        let processing_clone = Arc::clone(&self.is_processing);
-        let http_clone = ctx.http.clone();
-
-        tokio::spawn(async move {
-            let already_running = {
-                let mut is_proc = processing_clone.lock().await;
-                if *is_proc {
-                    true
-                } else {
-                    *is_proc = true;
-                    false
-                }
-            };
-
-            if already_running {
-                if let Some(chan) = source_channel {
-                    let _ = chan.say(&http_clone, "[WARN] Queue Manager: Already in progress.").await;
-                }
-                return;
-            }
-
-            if let Some(chan) = source_channel {
-                    let _ = chan.say(&http_clone, "[INFO] Queue Manager: Starting automation sequence...").await;
-            }
-
-            loop {
-                // Check if we were told to stop
-                {
-                    let is_proc = processing_clone.lock().await;
-                    if !*is_proc { break; }
-                }
-
-                let next_account = {
-                    let db = db_clone.lock().await;
-                    let mut accs: Vec<Account> = db.data.accounts.iter()
-                        .filter(|a| a.status != "done" && (!a.status.starts_with("error") || a.status.contains("Retrying")))
-                        .cloned()
-                        .collect();
-                    
-                    if let Some(uid) = &user_id_filter {
-                        accs.retain(|a| a.user_id.as_deref() == Some(uid));
-                    }
-                    
-                    // Explicitly prioritize:
-                    // 1. Pending accounts (in insertion order)
-                    // 2. Error/Retrying accounts (in insertion order)
-                    let (mut pending, errors): (Vec<Account>, Vec<Account>) = accs.into_iter()
-                        .partition(|a| !a.status.starts_with("error"));
-                    
-                    pending.extend(errors);
-                    pending.into_iter().next()
-                };
-
-                let acc = match next_account {
-                    Some(a) => a,
-                    None => break,
-                };
-                
-                let cookie = {
-                    let db = db_clone.lock().await;
-                    db.data.settings.cookies.clone().unwrap_or_default()
-                };
-
-                if cookie.is_empty() {
-                     break;
-                }
-
-                match EvertextClient::connect(&cookie).await {
-                    Ok(mut client) => {
-                        let decrypted_code = acc.decrypt_code();
-                        match client.run_loop(&acc, &decrypted_code, RunMode::Daily).await {
-                             Ok(_) => {
-                                {
-                                    let mut db = db_clone.lock().await;
-                                    let _ = db.update_status(&acc.name, "done");
-                                }
-                                if let Some(chan) = source_channel {
-                                    let _ = chan.say(&http_clone, format!("[SUCCESS] **{}** completed.", acc.name)).await;
-                                }
-                                Self::log_message(Arc::clone(&db_clone), Arc::clone(&http_clone), format!("[SUCCESS] Automation: **{}** completed successfully.", acc.name), source_channel).await;
-                            },
-                            Err(e) => {
-                                let err_str = e.to_string();
-                                
-                                if err_str.contains("SESSION_COMPLETE") {
-                                    {
-                                        let mut db = db_clone.lock().await;
-                                        let _ = db.update_status(&acc.name, "done");
-                                    }
-                                    if let Some(chan) = source_channel {
-                                        let _ = chan.say(&http_clone, format!("[SUCCESS] **{}** completed.", acc.name)).await;
-                                    }
-                                    Self::log_message(Arc::clone(&db_clone), Arc::clone(&http_clone), format!("[SUCCESS] Automation: **{}** completed through prompt flow.", acc.name), source_channel).await;
-
-                                } else if err_str.contains("INVALID_COMMAND_RESTART") {
-                                    if let Some(chan) = source_channel {
-                                         let _ = chan.say(&http_clone, format!("[WARN] Invalid Command on **{}**. Restarting session immediately.", acc.name)).await;
-                                    }
-                                    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-
-                                } else if err_str.contains("ZIGZA_DETECTED") {
-                                    if let Some(chan) = source_channel {
-                                        let _ = chan.say(&http_clone, format!("[WARN] Zigza error on **{}**. Waiting 10 mins before retry.", acc.name)).await;
-                                    }
-                                    Self::log_message(Arc::clone(&db_clone), Arc::clone(&http_clone), format!("[WARN] Automation: Zigza detected on **{}**. Retrying in 10m.", acc.name), source_channel).await;
-                                    {
-                                        let mut db = db_clone.lock().await;
-                                        let _ = db.update_status(&acc.name, "error: Zigza Retrying");
-                                    }
-                                    tokio::time::sleep(tokio::time::Duration::from_secs(600)).await;
-
-                                } else if err_str.contains("SERVER_FULL") {
-                                    if let Some(chan) = source_channel {
-                                        let _ = chan.say(&http_clone, format!("[WARN] Server Full. Retrying **{}** in 5 mins.", acc.name)).await;
-                                    }
-                                    Self::log_message(Arc::clone(&db_clone), Arc::clone(&http_clone), format!("[WARN] Automation: Server full. Retrying **{}** in 5m.", acc.name), source_channel).await;
-                                    tokio::time::sleep(tokio::time::Duration::from_secs(300)).await;
-
-                                } else if err_str.contains("LOGIN_REQUIRED") {
-                                    if let Some(chan) = source_channel {
-                                        let _ = chan.say(&http_clone, "⚠️ **CRITICAL: Session cookie expired!** Stopping queue.").await;
-                                    }
-                                    Self::log_message(Arc::clone(&db_clone), Arc::clone(&http_clone), "⚠️ **[CRITICAL] Automation: Session cookie expired!** Stopping queue.".to_string(), source_channel).await;
-                                    break;
-
-                                } else if err_str.contains("IDLE_TIMEOUT") || err_str.contains("CONNECTION_FAILED") || err_str.contains("SERVER_DISCONNECT") || err_str.contains("Connection handshake timed out") {
-                                    if let Some(chan) = source_channel {
-                                        let _ = chan.say(&http_clone, format!("[WARN] Connection issue on **{}** (Reason: {}). Retrying in 5s...", acc.name, err_str)).await;
-                                    }
-                                    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-
-                                } else {
-                                    {
-                                        let mut db = db_clone.lock().await;
-                                        let _ = db.update_status(&acc.name, &format!("error: {}", err_str));
-                                    }
-                                    if let Some(chan) = source_channel {
-                                        let _ = chan.say(&http_clone, format!("[ERROR] **{}** failed: {}", acc.name, err_str)).await;
-                                    }
-                                    Self::log_message(Arc::clone(&db_clone), Arc::clone(&http_clone), format!("[ERROR] Automation: **{}** failed. Reason: {}", acc.name, err_str), source_channel).await;
-                                }
-                            }
-                        }
-                    },
-                    Err(e) => {
-                        if let Some(chan) = source_channel {
-                            let _ = chan.say(&http_clone, format!("[ERROR] Connection failed for **{}**: {}", acc.name, e)).await;
-                        }
-                        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-                    }
-                }
-                // Small delay to prevent tight loops in edge cases
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-            }
-
-            {
-                let mut is_proc = processing_clone.lock().await;
-                *is_proc = false;
-            }
-            if let Some(chan) = source_channel {
-                let _ = chan.say(&http_clone, "[INFO] Queue Manager: Processing finished.").await;
-            }
-        });
+    fn process_queue(&self, ctx: &Context, user_id_filter: Option<String>, source_channel: Option<ChannelId>) {
+        runner::spawn_manager(Arc::clone(&self.db), Arc::clone(&self.pool), ctx.http.clone(), QueueKind::Daily(user_id_filter), source_channel);
     }
 
-    async fn process_handout_queue(&self, ctx: Context, source_channel: Option<ChannelId>) {
-        let db_clone = Arc::clone(&self.db);
-        let processing_clone = Arc::clone(&self.is_processing);
-        let http_clone = ctx.http.clone();
-
-        tokio::spawn(async move {
-            {
-                let mut is_proc = processing_clone.lock().await;
-                if *is_proc {
-                    if let Some(chan) = source_channel {
-                        let _ = chan.say(&http_clone, "[WARN] Manager: Already in progress.").await;
-                    }
-                    return;
-                }
-                *is_proc = true;
-            }
-
-            if let Some(chan) = source_channel {
-                let _ = chan.say(&http_clone, "[INFO] Handout Manager: Starting routine...").await;
-            }
-
-            let accounts = {
-                let db = db_clone.lock().await;
-                db.get_handout_accounts()
-            };
-
-            for acc in accounts {
-                 { 
-                    let is_proc = processing_clone.lock().await;
-                    if !*is_proc { break; }
-                }
-                
-                let cookie = {
-                    let db = db_clone.lock().await;
-                    db.data.settings.cookies.clone().unwrap_or_default()
-                };
-                if cookie.is_empty() { break; }
-
-                if let Some(chan) = source_channel {
-                     let _ = chan.say(&http_clone, format!("[INFO] Handout: Processing **{}**...", acc.name)).await;
-                }
-
-                match EvertextClient::connect(&cookie).await {
-                    Ok(mut client) => {
-                         let decrypted_code = acc.decrypt_code();
-                         match client.run_loop(&acc, &decrypted_code, RunMode::Handout).await {
-                             Ok(_) => {
-                                 if let Some(chan) = source_channel {
-                                     let _ = chan.say(&http_clone, format!("[SUCCESS] Handout **{}** completed.", acc.name)).await;
-                                 }
-                             },
-                             Err(e) => {
-                                 let err_str = e.to_string();
-                                 if err_str.contains("SESSION_COMPLETE") {
-                                      if let Some(chan) = source_channel {
-                                         let _ = chan.say(&http_clone, format!("[SUCCESS] Handout **{}** completed.", acc.name)).await;
-                                     }
-                                 } else if let Some(chan) = source_channel {
-                                     let _ = chan.say(&http_clone, format!("[ERROR] Handout **{}** failed: {}", acc.name, err_str)).await;
-                                 }
-                             }
-                         }
-                    },
-                    Err(e) => {
-                         if let Some(chan) = source_channel {
-                             let _ = chan.say(&http_clone, format!("[ERROR] Connection failed for **{}**: {}", acc.name, e)).await;
-                         }
-                    }
-                }
-                tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-            }
-
-            {
-                let mut is_proc = processing_clone.lock().await;
-                *is_proc = false;
-            }
-            if let Some(chan) = source_channel {
-                let _ = chan.say(&http_clone, "[INFO] Handout Manager: Finished.").await;
-            }
-        });
+    fn process_handout_queue(&self, ctx: &Context, source_channel: Option<ChannelId>) {
+        runner::spawn_manager(Arc::clone(&self.db), Arc::clone(&self.pool), ctx.http.clone(), QueueKind::Handout, source_channel);
     }
 }
 
@@ -332,13 +89,48 @@ impl EventHandler for Handler {
                 .description("List only your accounts"),
             CreateCommand::new("toggle_ping")
                 .description("Toggle ping notifications for your accounts"),
+            CreateCommand::new("configure_account")
+                .description("Set potion, refill and event choices for an account")
+                .add_option(CreateCommandOption::new(CommandOptionType::String, "name", "Account Name").required(true))
+                .add_option(CreateCommandOption::new(CommandOptionType::Integer, "potion_slot", "Potion to refill with (default 3)").required(false).min_int_value(1).max_int_value(3))
+                .add_option(CreateCommandOption::new(CommandOptionType::Integer, "refill_count", "Potions per refill (default 1)").required(false).min_int_value(1).max_int_value(10))
+                .add_option(CreateCommandOption::new(CommandOptionType::Integer, "max_refills", "Refills per run, -1 for unlimited (default unlimited)").required(false).max_int_value(20))
+                .add_option(CreateCommandOption::new(CommandOptionType::Boolean, "spend_on_events", "Spend mana on event stages (default yes)").required(false))
+                .add_option(CreateCommandOption::new(CommandOptionType::String, "next_mode", "Answer to the first 'next event' prompt (default auto)").required(false)
+                    .add_string_choice("auto", "auto")
+                    .add_string_choice("manual", "manual")),
+            CreateCommand::new("history")
+                .description("Show recent runs of an account")
+                .add_option(CreateCommandOption::new(CommandOptionType::String, "name", "Account Name").required(true))
+                .add_option(CreateCommandOption::new(CommandOptionType::Integer, "days", "How many days back (default 7)").required(false).min_int_value(1).max_int_value(90)),
+            CreateCommand::new("transcript")
+                .description("Upload the terminal transcript of a run")
+                .add_option(CreateCommandOption::new(CommandOptionType::String, "name", "Account Name").required(true))
+                .add_option(CreateCommandOption::new(CommandOptionType::Integer, "run", "Run number from /history (default 1 = latest)").required(false).min_int_value(1)),
             CreateCommand::new("force_run")
                 .description("Force run automation. Use 'all' to run all your accounts.")
                 .add_option(CreateCommandOption::new(CommandOptionType::String, "name", "Account Name or 'all'").required(false)),
             CreateCommand::new("force_run_all")
                 .description("[ADMIN] Run all accounts in the system"),
+            CreateCommand::new("queue_status")
+                .description("Show what the bot is running right now"),
+            CreateCommand::new("stop")
+                .description("Stop a running account")
+                .add_option(CreateCommandOption::new(CommandOptionType::String, "name", "Account Name").required(true)),
             CreateCommand::new("force_stop_all")
                 .description("[ADMIN] Stop all running processes"),
+            CreateCommand::new("set_concurrency")
+                .description("[ADMIN] Set how many accounts may run at the same time")
+                .add_option(CreateCommandOption::new(CommandOptionType::Integer, "limit", "Max concurrent sessions (1-10)").required(true).min_int_value(1).max_int_value(10)),
+            CreateCommand::new("schedule")
+                .description("[ADMIN] View or change scheduled jobs")
+                .add_option(CreateCommandOption::new(CommandOptionType::SubCommand, "list", "Show all jobs and their next run"))
+                .add_option(CreateCommandOption::new(CommandOptionType::SubCommand, "set", "Set a job's cron expression and enable it")
+                    .add_sub_option(job_choice())
+                    .add_sub_option(CreateCommandOption::new(CommandOptionType::String, "cron", "Cron expression, e.g. '0 0 * * *' (min hour day month weekday)").required(true))
+                    .add_sub_option(CreateCommandOption::new(CommandOptionType::String, "timezone", "IANA timezone, e.g. Asia/Jakarta (default: keep current)").required(false)))
+                .add_option(CreateCommandOption::new(CommandOptionType::SubCommand, "disable", "Disable a job")
+                    .add_sub_option(job_choice())),
             CreateCommand::new("mute_bot")
                 .description("[ADMIN] Mute automatic bot messages"),
             CreateCommand::new("unmute_bot")
@@ -357,7 +149,20 @@ impl EventHandler for Handler {
                 .description("[ADMIN] List authorized users"),
             CreateCommand::new("set_cookies")
                 .description("[ADMIN] Set session cookie to bypass login")
-                .add_option(CreateCommandOption::new(CommandOptionType::String, "cookie", "The 'session' cookie value").required(true)),
+                .add_option(CreateCommandOption::new(CommandOptionType::String, "cookie", "The 'session' cookie value").required(true))
+                .add_option(CreateCommandOption::new(CommandOptionType::String, "label", "Name for this cookie in the pool (default: 'default'); reusing a label replaces it").required(false)),
+            CreateCommand::new("list_cookies")
+                .description("[ADMIN] List the session cookie pool"),
+            CreateCommand::new("remove_cookie")
+                .description("[ADMIN] Remove a session cookie from the pool")
+                .add_option(CreateCommandOption::new(CommandOptionType::String, "label", "Cookie label").required(true)),
+            CreateCommand::new("check_cookie")
+                .description("[ADMIN] Test session cookies without logging into an account")
+                .add_option(CreateCommandOption::new(CommandOptionType::String, "label", "Only check this cookie (default: all)").required(false)),
+            CreateCommand::new("rotate_key")
+                .description("[OWNER] Re-encrypt all stored codes and cookies under a new ENCRYPTION_KEY")
+                .add_option(CreateCommandOption::new(CommandOptionType::String, "old_key", "The current ENCRYPTION_KEY").required(true))
+                .add_option(CreateCommandOption::new(CommandOptionType::String, "new_key", "The new key").required(true)),
             CreateCommand::new("ho_add")
                 .description("[ADMIN] Add account to Handout list")
                 .add_option(CreateCommandOption::new(CommandOptionType::String, "name", "Account Name").required(true)),
@@ -372,64 +177,10 @@ impl EventHandler for Handler {
 
         println!("[INFO] Discord: Slash commands registered successfully");
 
-        // Start Scheduler
-        let db_clone = Arc::clone(&self.db);
-        let ctx_clone = ctx.clone();
-        let is_processing_clone = Arc::clone(&self.is_processing);
-        
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
-            
-            loop {
-                interval.tick().await;
-                let now = Utc::now();
-                let today = now.format("%Y-%m-%d").to_string();
-                
-                // Daily Reset at 00:00 UTC (05:30 IST)
-                // Check database-persisted last_reset_date to prevent double-trigger on restart
-                let last_reset = {
-                    let db = db_clone.lock().await;
-                    db.data.settings.last_reset_date.clone()
-                };
-                
-                if now.hour() == 0 && now.minute() == 0 && last_reset.as_deref() != Some(&today) {
-                    println!("[INFO] Scheduler: Daily reset triggered at {} UTC", now);
-                    
-                    // Update database FIRST to prevent race conditions
-                    {
-                        let mut db = db_clone.lock().await;
-                        db.data.settings.last_reset_date = Some(today);
-                        let _ = db.save();
-                        let _ = db.reset_all_statuses();
-                    }
-                    
-                    // Trigger queue for all accounts
-                     let db_c = Arc::clone(&db_clone);
-                     let proc_c = Arc::clone(&is_processing_clone);
-                     let ctx_c = ctx_clone.clone();
-
-                     tokio::spawn(async move {
-                         let h = Handler { db: db_c, is_processing: proc_c };
-                         h.process_queue(ctx_c, None, None).await;
-                     });
-                }
-                
-                /* Handout Routine Removed per User Request (Review id: 191)
-                // Handout Routine (11:00 UTC = 18:00 Jakarta)
-                if now.hour() == 11 && now.minute() == 0 {
-                    println!("[INFO] Scheduler: Handout routine triggered at {} UTC", now);
-                     let db_c = Arc::clone(&db_clone);
-                     let proc_c = Arc::clone(&is_processing_clone);
-                     let ctx_c = ctx_clone.clone();
-
-                     tokio::spawn(async move {
-                         let h = Handler { db: db_c, is_processing: proc_c };
-                         h.process_handout_queue(ctx_c, None).await;
-                     });
-                }
-                */
-            }
-        });
+        // Start Scheduler (jobs and their cron expressions live in settings.schedules)
+        if !self.scheduler_started.swap(true, Ordering::SeqCst) {
+            runner::spawn_scheduler(Arc::clone(&self.db), Arc::clone(&self.pool), ctx.http.clone());
+        }
     }
 
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
@@ -444,52 +195,7 @@ impl EventHandler for Handler {
                     if db.data.accounts.is_empty() {
                         content = "No accounts registered.".to_string();
                     } else {
-                        // Create a nice embed
-                        let mut embed = CreateEmbed::new()
-                            .title("📋 Configured Accounts")
-                            .color(0x00ff00)
-                            .timestamp(Timestamp::now());
-
-                        let mut description = String::new();
-                        
-                        for acc in &db.data.accounts {
-                            let status_emoji = if acc.status == "done" { "✅" } 
-                                             else if acc.status.starts_with("error") { "❌" } 
-                                             else if acc.status == "pending" { "⏳" }
-                                             else { "💤" };
-                            
-                            let last_run_str = if let Some(lr) = &acc.last_run {
-                                if let Ok(parsed) = chrono::DateTime::parse_from_rfc3339(lr) {
-                                    format!("<t:{}:R>", parsed.timestamp())
-                                } else {
-                                    "Invalid Date".to_string()
-                                }
-                            } else {
-                                "Never".to_string()
-                            };
-
-                            let server_info = acc.target_server.as_deref().unwrap_or("Default");
-                            
-                            // Format: **Name** (Server)
-                            // Status: emoji Status
-                            // Last Run: time
-                            description.push_str(&format!(
-                                "**{}** ({})\n{} {} • 🕒 {}\n\n", 
-                                acc.name, 
-                                server_info,
-                                status_emoji, 
-                                acc.status, 
-                                last_run_str
-                            ));
-                        }
-
-                        // Check length limit for description (4096 chars)
-                        if description.len() > 4000 {
-                            description.truncate(4000);
-                            description.push_str("\n... (truncated)");
-                        }
-                        
-                        embed = embed.description(description);
+                        let embed = embeds::account_list("📋 Configured Accounts".to_string(), 0x00ff00, &db.data.accounts, true);
 
                         let _ = command.create_response(&ctx.http, CreateInteractionResponse::Message(
                             CreateInteractionResponseMessage::new().add_embed(embed)
@@ -504,44 +210,7 @@ impl EventHandler for Handler {
                     if my_accs.is_empty() {
                          content = "You have no accounts registered.".to_string();
                     } else {
-                        let mut embed = CreateEmbed::new()
-                            .title(format!("👤 Accounts for {}", command.user.name))
-                            .color(0x3498db)
-                            .timestamp(Timestamp::now());
-
-                         let mut description = String::new();
-                        
-                        for acc in &my_accs {
-                            let status_emoji = if acc.status == "done" { "✅" } 
-                                             else if acc.status.starts_with("error") { "❌" } 
-                                             else if acc.status == "pending" { "⏳" }
-                                             else { "💤" };
-                            
-                            let last_run_str = if let Some(lr) = &acc.last_run {
-                                if let Ok(parsed) = chrono::DateTime::parse_from_rfc3339(lr) {
-                                    format!("<t:{}:R>", parsed.timestamp())
-                                } else {
-                                    "Invalid Date".to_string()
-                                }
-                            } else {
-                                "Never".to_string()
-                            };
-
-                            description.push_str(&format!(
-                                "**{}**\n{} {} • 🕒 {}\n\n", 
-                                acc.name, 
-                                status_emoji, 
-                                acc.status, 
-                                last_run_str
-                            ));
-                        }
-                        
-                         if description.len() > 4000 {
-                            description.truncate(4000);
-                            description.push_str("\n... (truncated)");
-                        }
-                        
-                        embed = embed.description(description);
+                        let embed = embeds::account_list(format!("👤 Accounts for {}", command.user.name), 0x3498db, &my_accs, false);
 
                         let _ = command.create_response(&ctx.http, CreateInteractionResponse::Message(
                             CreateInteractionResponseMessage::new().add_embed(embed)
@@ -556,23 +225,26 @@ impl EventHandler for Handler {
                     
                     {
                         let mut db = self.db.lock().await;
-                        let encrypted_code = Account::encrypt_code_str(&code); // Encrypt!
+                        let encrypted_code = match db.encrypt_secret(&code) {
+                            Ok(encrypted) => encrypted,
+                            Err(e) => {
+                                let _ = command.create_response(&ctx.http, CreateInteractionResponse::Message(
+                                    CreateInteractionResponseMessage::new().content(format!("Cannot store the restore code: {}.", e)).ephemeral(true)
+                                )).await;
+                                return;
+                            }
+                        };
                         let new_acc = Account {
-                            name: name.clone(),
-                            code: encrypted_code,
                             target_server: server,
                             user_id: Some(user_id.clone()),
                             username: Some(command.user.name.clone()),
                             discord_nickname: command.member.as_ref().and_then(|m| m.nick.clone()),
-                            ping_enabled: false,
-                            handout_enabled: false,
-                            status: "pending".to_string(),
-                            last_run: None,
+                            ..Account::new(&name, encrypted_code)
                         };
                         let _ = db.add_account(new_acc);
                     }
                     content = format!("Successfully added account **{}**.", name);
-                    self.process_queue(ctx.clone(), Some(user_id), Some(command.channel_id)).await;
+                    self.process_queue(&ctx, Some(user_id), Some(command.channel_id));
                 },
                 "remove_account" => {
                     let mut db = self.db.lock().await;
@@ -589,6 +261,139 @@ impl EventHandler for Handler {
                         Err(e) => content = format!("Error: {}", e),
                     }
                 },
+                "configure_account" => {
+                    let option = |n: &str| command.data.options.iter().find(|o| o.name == n).map(|o| o.value.clone());
+                    let name = option("name").and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default();
+                    let potion_slot = option("potion_slot").and_then(|v| v.as_i64());
+                    let refill_count = option("refill_count").and_then(|v| v.as_i64());
+                    let max_refills = option("max_refills").and_then(|v| v.as_i64());
+                    let spend_on_events = option("spend_on_events").and_then(|v| v.as_bool());
+                    let next_mode = option("next_mode").and_then(|v| v.as_str().map(str::to_string));
+
+                    let owner = {
+                        let db = self.db.lock().await;
+                        db.data.accounts.iter().find(|a| a.name == name).map(|a| a.user_id.clone())
+                    };
+                    match owner {
+                        None => content = format!("Account **{}** not found.", name),
+                        Some(owner) if owner.as_deref() != Some(user_id.as_str()) && !self.is_admin(&ctx, &command).await => {
+                            content = "You can only configure your own accounts.".to_string();
+                        },
+                        Some(_) if potion_slot.is_some_and(|v| !(1..=3).contains(&v))
+                            || refill_count.is_some_and(|v| !(1..=10).contains(&v))
+                            || max_refills.is_some_and(|v| !(-1..=20).contains(&v)) => {
+                            content = "Invalid value: potion slot is 1-3, refill count 1-10, max refills -1 to 20.".to_string();
+                        },
+                        Some(_) => {
+                            let mut db = self.db.lock().await;
+                            let result = db.edit_account(&name, |acc| {
+                                if let Some(v) = potion_slot { acc.potion_slot = Some(v as u8); }
+                                if let Some(v) = refill_count { acc.refill_count = Some(v as u32); }
+                                if let Some(v) = max_refills { acc.max_refills = u32::try_from(v).ok(); }
+                                if let Some(v) = spend_on_events { acc.spend_on_events = Some(v); }
+                                if let Some(v) = &next_mode {
+                                    acc.next_mode = Some(if v == "manual" { NextMode::Manual } else { NextMode::Auto });
+                                }
+                            });
+                            match (result, db.data.accounts.iter().find(|a| a.name == name)) {
+                                (Ok(true), Some(acc)) => {
+                                    content = format!(
+                                        "⚙️ Profile for **{}**:\n• Potion slot: {}\n• Refill count: {}\n• Max refills per run: {}\n• Spend mana on events: {}\n• Next event: {}",
+                                        acc.name,
+                                        acc.potion(),
+                                        acc.refills(),
+                                        acc.max_refills.map(|m| m.to_string()).unwrap_or_else(|| "unlimited".to_string()),
+                                        if acc.spends_on_events() { "yes" } else { "no" },
+                                        match acc.next() { NextMode::Auto => "auto", NextMode::Manual => "manual" }
+                                    );
+                                },
+                                (Err(e), _) => content = format!("Error: {}", e),
+                                _ => content = format!("Account **{}** not found.", name),
+                            }
+                        },
+                    }
+                },
+                "history" => {
+                    let name = command.data.options.iter().find(|o| o.name == "name").and_then(|o| o.value.as_str()).unwrap_or("").to_string();
+                    let days = command.data.options.iter().find(|o| o.name == "days").and_then(|o| o.value.as_i64()).unwrap_or(7).clamp(1, 90) as u32;
+
+                    let owner = {
+                        let db = self.db.lock().await;
+                        db.data.accounts.iter().find(|a| a.name == name).map(|a| a.user_id.clone())
+                    };
+                    match owner {
+                        None => content = format!("Account **{}** not found.", name),
+                        Some(owner) if owner.as_deref() != Some(user_id.as_str()) && !self.is_admin(&ctx, &command).await => {
+                            content = "You can only view the history of your own accounts.".to_string();
+                        },
+                        Some(_) => {
+                            let runs = {
+                                let db = self.db.lock().await;
+                                db.get_history(&name, days)
+                            };
+                            if runs.is_empty() {
+                                content = format!("No runs recorded for **{}** in the last {} days.", name, days);
+                            } else {
+                                let embed = embeds::run_history(&name, days, &runs);
+
+                                let _ = command.create_response(&ctx.http, CreateInteractionResponse::Message(
+                                    CreateInteractionResponseMessage::new().add_embed(embed)
+                                )).await;
+                                return;
+                            }
+                        },
+                    }
+                },
+                "transcript" => {
+                    let name = command.data.options.iter().find(|o| o.name == "name").and_then(|o| o.value.as_str()).unwrap_or("").to_string();
+                    let run_no = command.data.options.iter().find(|o| o.name == "run").and_then(|o| o.value.as_i64()).unwrap_or(1).max(1) as usize;
+
+                    let owner = {
+                        let db = self.db.lock().await;
+                        db.data.accounts.iter().find(|a| a.name == name).map(|a| a.user_id.clone())
+                    };
+                    match owner {
+                        None => content = format!("Account **{}** not found.", name),
+                        Some(owner) if owner.as_deref() != Some(user_id.as_str()) && !self.is_admin(&ctx, &command).await => {
+                            content = "You can only view transcripts of your own accounts.".to_string();
+                        },
+                        Some(_) => {
+                            // Same numbering as /history: 1 = newest run
+                            let run = {
+                                let db = self.db.lock().await;
+                                db.data.history.iter().rev().filter(|r| r.account == name).nth(run_no - 1).cloned()
+                            };
+                            match run {
+                                None => content = format!("No run #{} recorded for **{}**.", run_no, name),
+                                Some(run) => match run.transcript.as_deref().map(|p| (p, std::fs::read(p))) {
+                                    None => content = format!("Run #{} of **{}** ({}) has no transcript.", run_no, name, run.outcome),
+                                    Some((path, Err(e))) => content = format!("Could not read transcript `{}`: {}", path, e),
+                                    Some((path, Ok(mut bytes))) => {
+                                        // Stay under Discord's upload limit; the end of a session is the interesting part
+                                        let mut note = String::new();
+                                        if bytes.len() > MAX_TRANSCRIPT_UPLOAD {
+                                            bytes.drain(..bytes.len() - MAX_TRANSCRIPT_UPLOAD);
+                                            note = " (only the last 8 MB)".to_string();
+                                        }
+                                        let file_name = std::path::Path::new(path).file_name()
+                                            .map(|f| f.to_string_lossy().into_owned())
+                                            .unwrap_or_else(|| "transcript.log".to_string());
+                                        let started = chrono::DateTime::parse_from_rfc3339(&run.started_at)
+                                            .map(|t| format!("<t:{}:f>", t.timestamp()))
+                                            .unwrap_or_else(|_| run.started_at.clone());
+
+                                        let _ = command.create_response(&ctx.http, CreateInteractionResponse::Message(
+                                            CreateInteractionResponseMessage::new()
+                                                .content(format!("📜 **{}** run #{} • {} • {} • {}{}", name, run_no, started, run.mode, run.outcome, note))
+                                                .add_file(CreateAttachment::bytes(bytes, file_name))
+                                        )).await;
+                                        return;
+                                    },
+                                },
+                            }
+                        },
+                    }
+                },
                 "force_run" => {
                     let name = command.data.options.iter().find(|o| o.name == "name").and_then(|o| o.value.as_str());
                     
@@ -596,68 +401,11 @@ impl EventHandler for Handler {
                     
                     if target_name.to_lowercase() == "all" {
                         // Run all for THIS user
-                        self.process_queue(ctx.clone(), Some(user_id), Some(command.channel_id)).await;
+                        self.process_queue(&ctx, Some(user_id), Some(command.channel_id));
                         content = "Queued all your accounts for execution.".to_string();
                     } else {
                         // Start single
-                        let db_clone = Arc::clone(&self.db);
-                        let processing_clone = Arc::clone(&self.is_processing);
-                        let http_clone = ctx.http.clone();
-                        let channel_id = command.channel_id;
-                        let n_owned = target_name.to_string();
-                        
-                         tokio::spawn(async move {
-                            let (cookie, acc) = {
-                                let mut is_proc = processing_clone.lock().await;
-                                if *is_proc {
-                                    let _ = channel_id.say(&http_clone, "[WARN] Already in progress.").await;
-                                    return;
-                                }
-                                *is_proc = true;
-                                
-                                let db = db_clone.lock().await;
-                                (db.data.settings.cookies.clone().unwrap_or_default(), 
-                                 db.data.accounts.iter().find(|a| a.name == n_owned).cloned())
-                            };
-                            
-                            if let Some(acc) = acc {
-                                if cookie.is_empty() {
-                                    let _ = channel_id.say(&http_clone, "[ERROR] No cookies set.").await;
-                                } else {
-                                    let _ = channel_id.say(&http_clone, format!("[INFO] Force running **{}**...", acc.name)).await;
-                                    match EvertextClient::connect(&cookie).await {
-                                        Ok(mut client) => {
-                                            let decrypted_code = acc.decrypt_code();
-                                            match client.run_loop(&acc, &decrypted_code, RunMode::Daily).await {
-                                                Ok(_) => {
-                                                    let mut db = db_clone.lock().await;
-                                                    let _ = db.update_status(&acc.name, "done");
-                                                    let _ = channel_id.say(&http_clone, format!("[SUCCESS] **{}** finished.", acc.name)).await;
-                                                },
-                                                Err(e) => {
-                                                    let err_str = e.to_string();
-                                                    if err_str.contains("SESSION_COMPLETE") {
-                                                        let mut db = db_clone.lock().await;
-                                                        let _ = db.update_status(&acc.name, "done");
-                                                        let _ = channel_id.say(&http_clone, format!("[SUCCESS] **{}** finished.", acc.name)).await;
-                                                    } else {
-                                                        let _ = channel_id.say(&http_clone, format!("[ERROR] **{}** failed: {}", acc.name, err_str)).await;
-                                                    }
-                                                }
-                                            }
-                                        },
-                                        Err(e) => {
-                                            let _ = channel_id.say(&http_clone, format!("[ERROR] Connection failed: {}", e)).await;
-                                        }
-                                    }
-                                }
-                            } else {
-                                let _ = channel_id.say(&http_clone, format!("[ERROR] Account **{}** not found.", n_owned)).await;
-                            }
-                            
-                            let mut is_proc = processing_clone.lock().await;
-                            *is_proc = false;
-                        });
+                        runner::force_run(Arc::clone(&self.db), Arc::clone(&self.pool), ctx.http.clone(), command.channel_id, target_name.to_string());
                         content = format!("Force run initiated for **{}**.", target_name);
                     }
                 },
@@ -665,17 +413,54 @@ impl EventHandler for Handler {
                     if !self.is_admin(&ctx, &command).await {
                         content = "Admin permissions required.".to_string();
                     } else {
-                        self.process_queue(ctx.clone(), None, Some(command.channel_id)).await;
+                        self.process_queue(&ctx, None, Some(command.channel_id));
                         content = "Starting ALL pending accounts...".to_string();
                     }
                 },
+                "queue_status" => {
+                    let embed = {
+                        let db = self.db.lock().await;
+                        let kind = QueueKind::Daily(None);
+                        embeds::queue_status(&QueueSnapshot::collect(&db, &self.pool, &kind, &HashMap::new(), Utc::now()), &kind, None, false)
+                    };
+                    let _ = command.create_response(&ctx.http, CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new().add_embed(embed)
+                    )).await;
+                    return;
+                },
+                "stop" => {
+                    let name = command.data.options.iter().find(|o| o.name == "name").and_then(|o| o.value.as_str()).unwrap_or("").to_string();
+                    let owner = {
+                        let db = self.db.lock().await;
+                        db.data.accounts.iter().find(|a| a.name == name).map(|a| a.user_id.clone())
+                    };
+                    match owner {
+                        None => content = format!("Account **{}** not found.", name),
+                        Some(owner) if owner.as_deref() != Some(user_id.as_str()) && !self.is_admin(&ctx, &command).await => {
+                            content = "You can only stop your own accounts.".to_string();
+                        },
+                        Some(_) => {
+                            // The db lock is held across the cancel so the daily queue can't pick the
+                            // account straight back up before it is parked. A stopped handout leaves
+                            // the daily status alone.
+                            let mut db = self.db.lock().await;
+                            content = match self.pool.cancel(&name) {
+                                Some(RunMode::Daily) => {
+                                    let _ = db.update_status(&name, "stopped");
+                                    format!("🛑 Stopping **{}**. It won't be retried until the next reset or /force_run.", name)
+                                },
+                                Some(RunMode::Handout) => format!("🛑 Stopping the handout run of **{}**.", name),
+                                None => format!("**{}** isn't running.", name),
+                            };
+                        },
+                    }
+                },
                 "force_stop_all" => {
                     if !self.is_admin(&ctx, &command).await {
                         content = "Admin permissions required.".to_string();
                     } else {
-                        let mut is_proc = self.is_processing.lock().await;
-                        *is_proc = false;
-                        content = "Queue processing halted.".to_string();
+                        self.pool.stop();
+                        content = "Queue processing halted. Running sessions are being stopped.".to_string();
                     }
                 },
 // ... Inside interaction_create match block
@@ -728,6 +513,85 @@ impl EventHandler for Handler {
                      }
                 },
                 // Existing commands...
+                "set_concurrency" => {
+                    if !self.is_admin(&ctx, &command).await {
+                        content = "Admin permissions required.".to_string();
+                    } else {
+                        let limit = command.data.options.iter().find(|o| o.name == "limit").and_then(|o| o.value.as_i64()).unwrap_or(0);
+                        if !(1..=10).contains(&limit) {
+                            content = "Limit must be between 1 and 10.".to_string();
+                        } else {
+                            let mut db = self.db.lock().await;
+                            match db.set_concurrency(limit as usize) {
+                                Ok(_) => content = format!("Up to **{}** accounts will now run at the same time.", limit),
+                                Err(e) => content = format!("Error: {}", e),
+                            }
+                        }
+                    }
+                },
+                "schedule" => {
+                    if !self.is_admin(&ctx, &command).await {
+                        content = "Admin permissions required.".to_string();
+                    } else if let Some(sub) = command.data.options.first() {
+                        let args = match &sub.value {
+                            CommandDataOptionValue::SubCommand(args) => args.clone(),
+                            _ => Vec::new(),
+                        };
+                        let arg = |n: &str| args.iter().find(|o| o.name == n).and_then(|o| o.value.as_str()).map(str::to_string);
+                        let job = arg("job").and_then(|n| Job::from_name(&n));
+
+                        let mut db = self.db.lock().await;
+                        scheduler::ensure_defaults(&mut db.data.settings);
+                        match (sub.name.as_str(), job) {
+                            ("list", _) => {
+                                let mut lines = Vec::new();
+                                for entry in &db.data.settings.schedules {
+                                    let next = match (entry.enabled, entry.next_run.as_deref().and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())) {
+                                        (false, _) => "disabled".to_string(),
+                                        (true, Some(t)) => format!("next <t:{}:f> (<t:{}:R>)", t.timestamp(), t.timestamp()),
+                                        (true, None) => "next run pending".to_string(),
+                                    };
+                                    let icon = if entry.enabled { "🟢" } else { "⚪" };
+                                    lines.push(format!("{} **{}** `{}` ({}) • {}", icon, entry.name, entry.cron, entry.timezone, next));
+                                }
+                                content = format!("🗓️ **Scheduled jobs**\n{}", lines.join("\n"));
+                            },
+                            ("set", Some(job)) => {
+                                let cron = arg("cron").unwrap_or_default();
+                                let timezone = arg("timezone");
+                                let entry = db.data.settings.schedules.iter_mut().find(|s| s.name == job.name()).expect("defaults ensured");
+                                let candidate = db::JobSchedule {
+                                    cron: cron.clone(),
+                                    timezone: timezone.unwrap_or_else(|| entry.timezone.clone()),
+                                    enabled: true,
+                                    ..entry.clone()
+                                };
+                                match scheduler::next_after(&candidate, Utc::now()) {
+                                    Ok(next) => {
+                                        let timezone = candidate.timezone.clone();
+                                        *entry = db::JobSchedule { next_run: Some(next.to_rfc3339()), ..candidate };
+                                        content = match db.save_settings() {
+                                            Ok(_) => format!("✅ **{}** now runs on `{}` ({}). Next run <t:{}:f>.", job.name(), cron, timezone, next.timestamp()),
+                                            Err(e) => format!("Error: {}", e),
+                                        };
+                                    },
+                                    Err(e) => content = format!("❌ {}", e),
+                                }
+                            },
+                            ("disable", Some(job)) => {
+                                if let Some(entry) = db.data.settings.schedules.iter_mut().find(|s| s.name == job.name()) {
+                                    entry.enabled = false;
+                                    entry.next_run = None;
+                                }
+                                content = match db.save_settings() {
+                                    Ok(_) => format!("⚪ **{}** disabled.", job.name()),
+                                    Err(e) => format!("Error: {}", e),
+                                };
+                            },
+                            _ => content = "Unknown job.".to_string(),
+                        }
+                    }
+                },
                 "mute_bot" => {
                     if !self.is_admin(&ctx, &command).await {
                         content = "Admin permissions required.".to_string();
@@ -781,16 +645,116 @@ impl EventHandler for Handler {
                     if !self.is_admin(&ctx, &command).await {
                         content = "Admin permissions required.".to_string();
                     } else {
+                        let label = command.data.options.iter().find(|o| o.name == "label").and_then(|o| o.value.as_str()).unwrap_or("default").to_string();
                         let mut db = self.db.lock().await;
                         if let Some(option) = command.data.options.iter().find(|o| o.name == "cookie") {
                             if let Some(cookie) = option.value.as_str() {
-                                db.data.settings.cookies = Some(cookie.to_string());
-                                let _ = db.save();
-                                content = "Session cookies updated.".to_string();
+                                content = match db.set_cookie(&label, cookie, Some(user_id.clone())) {
+                                    Ok(_) => format!("Session cookie `{}` saved. {} healthy cookie(s) in the pool.", label, db.healthy_cookies()),
+                                    Err(e) => format!("Error: {}", e),
+                                };
                             }
                         }
                     }
                 },
+                "list_cookies" => {
+                    if !self.is_admin(&ctx, &command).await {
+                        content = "Admin permissions required.".to_string();
+                    } else {
+                        let db = self.db.lock().await;
+                        if db.data.settings.session_cookies.is_empty() {
+                            content = "No session cookies set. Use /set_cookies.".to_string();
+                        } else {
+                            let mut lines = vec![format!("🍪 **Session cookies** (selection: {:?})", db.data.settings.cookie_selection)];
+                            for cookie in &db.data.settings.session_cookies {
+                                let validated = cookie.last_validated.as_deref()
+                                    .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+                                    .map(|t| format!("<t:{}:R>", t.timestamp()))
+                                    .unwrap_or_else(|| "never".to_string());
+                                lines.push(format!(
+                                    "{} **{}** `{}` • added by {} • validated {} • failures {}",
+                                    if cookie.quarantined { "🚫" } else { "✅" },
+                                    cookie.label,
+                                    cookie.masked(db.cipher().ok()),
+                                    cookie.added_by.as_deref().map(|u| format!("<@{}>", u)).unwrap_or_else(|| "?".to_string()),
+                                    validated,
+                                    cookie.failure_count
+                                ));
+                            }
+                            content = lines.join("\n");
+                        }
+                    }
+                },
+                "remove_cookie" => {
+                    if !self.is_admin(&ctx, &command).await {
+                        content = "Admin permissions required.".to_string();
+                    } else {
+                        let label = command.data.options.iter().find(|o| o.name == "label").and_then(|o| o.value.as_str()).unwrap_or("").to_string();
+                        let mut db = self.db.lock().await;
+                        content = match db.remove_cookie(&label) {
+                            Ok(true) => format!("Session cookie `{}` removed.", label),
+                            Ok(false) => format!("No session cookie labelled `{}`.", label),
+                            Err(e) => format!("Error: {}", e),
+                        };
+                    }
+                },
+                "check_cookie" => {
+                    if !self.is_admin(&ctx, &command).await {
+                        content = "Admin permissions required.".to_string();
+                    } else if let Some(reservation) = self.pool.reserve_all() {
+                        let label = command.data.options.iter().find(|o| o.name == "label").and_then(|o| o.value.as_str()).map(str::to_string);
+                        // A probe can take up to a minute per cookie
+                        let _ = command.defer(&ctx.http).await;
+                        let lines = runner::check_cookies(&self.db, &ctx.http, label.as_deref()).await;
+                        drop(reservation);
+                        let reply = if lines.is_empty() {
+                            match label {
+                                Some(label) => format!("No session cookie labelled `{}`.", label),
+                                None => "No session cookies set. Use /set_cookies.".to_string(),
+                            }
+                        } else {
+                            format!("🍪 **Cookie check**\n{}", lines.join("\n"))
+                        };
+                        let _ = command.edit_response(&ctx.http, EditInteractionResponse::new().content(reply)).await;
+                        return;
+                    } else {
+                        content = "Sessions are running or another check is in progress; checking a cookie would stop their game. Try again once the queue is idle.".to_string();
+                    }
+                },
+                "rotate_key" => {
+                    let is_owner = std::env::var("OWNER_ID").is_ok_and(|owner| owner == user_id);
+                    let key = |n: &str| command.data.options.iter().find(|o| o.name == n).and_then(|o| o.value.as_str()).unwrap_or("").to_string();
+                    let (old_key, new_key) = (key("old_key"), key("new_key"));
+                    let reply = if !is_owner {
+                        "Only the bot owner can rotate the encryption key.".to_string()
+                    } else if new_key.len() < 12 {
+                        "The new key must be at least 12 characters long.".to_string()
+                    } else {
+                        let mut db = self.db.lock().await;
+                        match db.rotate_key(&Cipher::new(&old_key), &Cipher::new(&new_key), &db::backup_dir()) {
+                            // The database now holds the new cipher; .env must follow or the next start can't decrypt anything
+                            Ok(r) => {
+                                let env = match crypto::store_env_key(std::path::Path::new(crypto::ENV_FILE), &new_key) {
+                                    Ok(()) => format!("ENCRYPTION_KEY in `{}` now holds the new key.", crypto::ENV_FILE),
+                                    Err(e) => {
+                                        println!("[WARN] Could not write the rotated key to {}: {}", crypto::ENV_FILE, e);
+                                        format!("⚠️ Could not update `{}` ({}). **Set ENCRYPTION_KEY to the new key now**, or the next restart can't decrypt anything.", crypto::ENV_FILE, e)
+                                    },
+                                };
+                                format!("🔐 Re-encrypted {} restore code(s) and {} cookie(s). Backup: `{}`\n{}", r.accounts, r.cookies, r.backup, env)
+                            },
+                            Err(e) => format!("❌ {}", e),
+                        }
+                    };
+                    // Keys must never show up in the channel
+                    let _ = command.create_response(&ctx.http, CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new().content(reply).ephemeral(true)
+                    )).await;
+                    if is_owner {
+                        bot::log_message(Arc::clone(&self.db), ctx.http.clone(), format!("🔐 <@{}> ran an encryption key rotation.", user_id), None).await;
+                    }
+                    return;
+                },
                 "ho_add" => {
                     if !self.is_admin(&ctx, &command).await {
                          content = "Admin permissions required.".to_string();
@@ -834,33 +798,7 @@ impl EventHandler for Handler {
                          if list.is_empty() {
                              content = "Handout List is empty.".to_string();
                          } else {
-                            let mut embed = CreateEmbed::new()
-                                .title("🎁 Handout List")
-                                .color(0xffa500) // Orange
-                                .timestamp(Timestamp::now());
-
-                            let mut description = String::new();
-                            
-                            for acc in list {
-                                let status_emoji = if acc.status == "done" { "✅" } 
-                                                 else if acc.status.starts_with("error") { "❌" } 
-                                                 else if acc.status == "pending" { "⏳" }
-                                                 else { "💤" };
-                                
-                                description.push_str(&format!(
-                                    "**{}** • {} {}\n", 
-                                    acc.name, 
-                                    status_emoji, 
-                                    acc.status
-                                ));
-                            }
-
-                            if description.len() > 4000 {
-                                description.truncate(4000);
-                                description.push_str("\n... (truncated)");
-                            }
-                            
-                            embed = embed.description(description);
+                            let embed = embeds::handout_list(&list);
 
                             let _ = command.create_response(&ctx.http, CreateInteractionResponse::Message(
                                 CreateInteractionResponseMessage::new().add_embed(embed)
@@ -873,7 +811,7 @@ impl EventHandler for Handler {
                     if !self.is_admin(&ctx, &command).await {
                          content = "Admin permissions required.".to_string();
                     } else {
-                         self.process_handout_queue(ctx.clone(), Some(command.channel_id)).await;
+                         self.process_handout_queue(&ctx, Some(command.channel_id));
                          content = "Starting Handout routine for all enabled accounts... Check logs.".to_string();
                     }
                 },
@@ -895,22 +833,41 @@ impl EventHandler for Handler {
 async fn main() {
     dotenv::dotenv().ok();
     env_logger::init();
-    
+
+    // Subcommands (run, list, status, import-json, rotate-key, db) work without Discord
+    if let Some(command) = cli::Cli::parse().command {
+        std::process::exit(cli::run(command).await);
+    }
+
     let token = std::env::var("DISCORD_TOKEN").expect("Expected a DISCORD_TOKEN in the environment");
     let database_res = Database::load();
     let database = match database_res {
-        Ok(db) => Arc::new(Mutex::new(db)),
+        Ok(mut db) => {
+            // Plaintext cookies (e.g. the legacy field) and older ciphertext are migrated on startup
+            match db.upgrade_secrets() {
+                Ok(0) => {},
+                Ok(upgraded) => match db.save() {
+                    Ok(()) => println!("[INFO] Re-encrypted {} stored secret(s) in the current format.", upgraded),
+                    Err(e) => println!("[ERROR] Failed to save re-encrypted secrets: {}", e),
+                },
+                Err(e) => println!("[ERROR] Stored secrets were not migrated: {}", e),
+            }
+            let secrets = db.check_secrets();
+            if !secrets.is_ok() {
+                println!("[ERROR] Some stored secrets are unusable or still in plaintext:\n{}", secrets.summary());
+            }
+            Arc::new(Mutex::new(db))
+        },
         Err(e) => {
-            println!("[CRITICAL] Failed to load database: {}. Bot may not function correctly.", e);
-            // We still need a database object to continue, so we'll try to create a dummy one if possible
-            // or just exit gracefully instead of panicking.
-            return; 
+            println!("[CRITICAL] Failed to load database: {}", e);
+            std::process::exit(1);
         }
     };
     
     let handler = Handler {
         db: database,
-        is_processing: Arc::new(Mutex::new(false)),
+        pool: Arc::new(WorkerPool::default()),
+        scheduler_started: AtomicBool::new(false),
     };
 
     let intents = GatewayIntents::GUILD_MESSAGES | GatewayIntents::DIRECT_MESSAGES | GatewayIntents::MESSAGE_CONTENT;
@@ -925,3 +882,11 @@ async fn main() {
         println!("Client error: {:?}", why);
     }
 }
+
+/// The `job` option shared by the /schedule subcommands.
+fn job_choice() -> CreateCommandOption {
+    Job::ALL.into_iter().fold(
+        CreateCommandOption::new(CommandOptionType::String, "job", "Scheduled job").required(true),
+        |option, job| option.add_string_choice(job.name(), job.name()),
+    )
+}
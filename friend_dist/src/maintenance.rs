@@ -0,0 +1,385 @@
+//! Offline checks and repairs of database files, used by the `db` subcommands.
+
+use serde_json::Value;
+
+use crate::cookies::SessionCookie;
+use crate::crypto::{Cipher, CryptoError};
+use crate::db::DbData;
+use crate::scheduler::{self, Job};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The bot would refuse to start, or would misbehave.
+    Error,
+    /// Works, but is probably not what the operator wants.
+    Warning,
+}
+
+/// One problem `validate` found.
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    /// Where, e.g. `account 'Nyx'` or `accounts[3].encryptedCode`.
+    pub location: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let level = match self.severity {
+            Severity::Error => "ERROR",
+            Severity::Warning => "WARN",
+        };
+        write!(f, "[{}] {}: {}", level, self.location, self.message)
+    }
+}
+
+fn finding(severity: Severity, location: impl Into<String>, message: impl Into<String>) -> Finding {
+    Finding { severity, location: location.into(), message: message.into() }
+}
+
+/// Checks a db.json document: JSON syntax, the schema, fields the bot ignores (and would drop
+/// on the next save), then everything `validate_data` checks.
+pub fn validate_json(raw: &str, key: Option<&Cipher>) -> Vec<Finding> {
+    let value: Value = match serde_json::from_str(raw) {
+        Ok(v) => v,
+        Err(e) => return vec![finding(Severity::Error, "file", format!("not valid JSON: {}", e))],
+    };
+    let data: DbData = match serde_json::from_value(value.clone()) {
+        Ok(d) => d,
+        Err(e) => return vec![finding(Severity::Error, "file", format!("does not match the database schema: {}", e))],
+    };
+    let mut findings = Vec::new();
+    if let Ok(known) = serde_json::to_value(&data) {
+        unknown_fields(&value, &known, "", &mut findings);
+    }
+    findings.extend(validate_data(&data, key));
+    findings
+}
+
+/// Fields present in `raw` but not in the re-serialized `known`. Empty values are skipped:
+/// optional fields that are empty aren't written back, so they would be false positives.
+fn unknown_fields(raw: &Value, known: &Value, path: &str, out: &mut Vec<Finding>) {
+    match (raw, known) {
+        (Value::Object(raw), Value::Object(known)) => {
+            for (field, value) in raw {
+                let here = if path.is_empty() { field.clone() } else { format!("{}.{}", path, field) };
+                match known.get(field) {
+                    Some(known) => unknown_fields(value, known, &here, out),
+                    None if !is_empty(value) => out.push(finding(Severity::Warning, here, "unknown field, dropped on the next save")),
+                    None => {},
+                }
+            }
+        },
+        (Value::Array(raw), Value::Array(known)) => {
+            for (i, (value, known)) in raw.iter().zip(known).enumerate() {
+                unknown_fields(value, known, &format!("{}[{}]", path, i), out);
+            }
+        },
+        _ => {},
+    }
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(s) => s.is_empty(),
+        Value::Array(a) => a.is_empty(),
+        Value::Object(o) => o.is_empty(),
+        _ => false,
+    }
+}
+
+/// Consistency checks on loaded data. Secrets are only checked when `key` is given.
+pub fn validate_data(data: &DbData, key: Option<&Cipher>) -> Vec<Finding> {
+    let mut out = Vec::new();
+    if key.is_none() {
+        out.push(finding(Severity::Warning, "ENCRYPTION_KEY", "not set, restore codes and cookies were not checked"));
+    }
+    let secret = |out: &mut Vec<Finding>, location: &str, what: &str, stored: &str| {
+        let Some(cipher) = key else { return };
+        match cipher.decrypt(stored) {
+            Ok(_) => {},
+            Err(CryptoError::NotEncrypted) => out.push(finding(Severity::Warning, location, format!("{} is stored in plaintext (run `db encrypt-codes`)", what))),
+            Err(e) => out.push(finding(Severity::Error, location, format!("{}: {}", what, e))),
+        }
+    };
+
+    let mut names: Vec<&str> = Vec::new();
+    for acc in &data.accounts {
+        let location = format!("account '{}'", acc.name);
+        if acc.name.trim().is_empty() {
+            out.push(finding(Severity::Error, &location, "empty name"));
+        }
+        if names.contains(&acc.name.as_str()) {
+            out.push(finding(Severity::Error, &location, "duplicate name, only the first entry is used"));
+        } else if let Some(other) = names.iter().find(|n| n.eq_ignore_ascii_case(&acc.name)) {
+            out.push(finding(Severity::Warning, &location, format!("differs from '{}' only in case", other)));
+        }
+        names.push(&acc.name);
+
+        if acc.code.is_empty() {
+            out.push(finding(Severity::Error, &location, "empty restore code"));
+        } else {
+            secret(&mut out, &location, "restore code", &acc.code);
+        }
+        for (field, value) in [("lastRun", &acc.last_run), ("nextAttemptAt", &acc.next_attempt_at)] {
+            if let Some(v) = value.as_deref().filter(|v| chrono::DateTime::parse_from_rfc3339(v).is_err()) {
+                out.push(finding(Severity::Warning, &location, format!("{} '{}' is not an RFC 3339 timestamp", field, v)));
+            }
+        }
+        if acc.potion_slot.is_some_and(|v| !(1..=3).contains(&v)) {
+            out.push(finding(Severity::Warning, &location, "potionSlot must be 1-3"));
+        }
+        if acc.refill_count.is_some_and(|v| !(1..=10).contains(&v)) {
+            out.push(finding(Severity::Warning, &location, "refillCount must be 1-10"));
+        }
+    }
+
+    let settings = &data.settings;
+    let legacy_cookie = settings.cookies.as_deref().is_some_and(|c| !c.is_empty());
+    if legacy_cookie {
+        out.push(finding(Severity::Warning, "settings.cookies", "legacy cookie field, moved into sessionCookies on the next start"));
+    } else if settings.session_cookies.is_empty() {
+        out.push(finding(Severity::Error, "settings.sessionCookies", "no session cookie, the bot will not start"));
+    }
+    let mut labels: Vec<&str> = Vec::new();
+    for cookie in &settings.session_cookies {
+        let location = format!("cookie '{}'", cookie.label);
+        if labels.contains(&cookie.label.as_str()) {
+            out.push(finding(Severity::Error, &location, "duplicate label"));
+        }
+        labels.push(&cookie.label);
+        secret(&mut out, &location, "value", &cookie.value);
+        if cookie.quarantined {
+            out.push(finding(Severity::Warning, &location, "quarantined"));
+        }
+    }
+
+    for job in &settings.schedules {
+        let location = format!("schedule '{}'", job.name);
+        if Job::from_name(&job.name).is_none() {
+            out.push(finding(Severity::Warning, &location, "unknown job, ignored by the scheduler"));
+        }
+        if let Err(e) = scheduler::parse_cron(&job.cron) {
+            out.push(finding(Severity::Error, &location, e));
+        }
+        if let Err(e) = scheduler::parse_timezone(&job.timezone) {
+            out.push(finding(Severity::Error, &location, e));
+        }
+    }
+    out
+}
+
+/// What `merge` did with the other file.
+#[derive(Debug, Default)]
+pub struct MergeReport {
+    /// Accounts copied over
+    pub added: Vec<String>,
+    /// Existing accounts that picked up fields from the other file
+    pub updated: Vec<String>,
+    /// Disagreements; the base file's value was kept
+    pub conflicts: Vec<String>,
+    /// Entries that were not merged, with the reason
+    pub skipped: Vec<String>,
+    pub cookies_added: Vec<String>,
+    pub admins_added: usize,
+    pub history_added: usize,
+}
+
+/// Merges `other` into `base`. Accounts match by name (ignoring case) or by restore code.
+/// Base values win; the other file only fills gaps, plus the newer `lastRun`/status and
+/// enabled ping/handout flags, as the old merge script did. Secrets from `other` are
+/// decrypted with `other_key` and stored re-encrypted with `base_key`.
+pub fn merge(base: &mut DbData, other: &DbData, base_key: &Cipher, other_key: &Cipher) -> MergeReport {
+    let mut report = MergeReport::default();
+    let mut base_codes: Vec<Option<String>> = base.accounts.iter()
+        .map(|a| base_key.decrypt(&a.code).ok().map(|c| c.trim().to_string()))
+        .collect();
+
+    for theirs in &other.accounts {
+        let code = match other_key.decrypt(&theirs.code) {
+            Ok(code) => code.trim().to_string(),
+            Err(e) => {
+                report.skipped.push(format!("account '{}': restore code {}", theirs.name, e));
+                continue;
+            },
+        };
+        let by_name = base.accounts.iter().position(|a| a.name.eq_ignore_ascii_case(&theirs.name));
+        let by_code = base_codes.iter().position(|c| c.as_deref() == Some(code.as_str()));
+        let Some(i) = by_name.or(by_code) else {
+            let mut acc = theirs.clone();
+            acc.code = base_key.encrypt(&code);
+            base.accounts.push(acc);
+            base_codes.push(Some(code));
+            report.added.push(theirs.name.clone());
+            continue;
+        };
+
+        let ours = &mut base.accounts[i];
+        if by_name.is_some() && base_codes[i].as_deref() != Some(code.as_str()) {
+            report.conflicts.push(format!("account '{}': different restore codes, kept the base one", ours.name));
+            continue;
+        }
+        if !ours.name.eq_ignore_ascii_case(&theirs.name) {
+            report.conflicts.push(format!("account '{}' has the same restore code as '{}', merged into '{}'", theirs.name, ours.name, ours.name));
+        }
+        for (field, a, b) in [("owner", &ours.user_id, &theirs.user_id), ("server", &ours.target_server, &theirs.target_server)] {
+            if let (Some(a), Some(b)) = (a, b) {
+                if a != b {
+                    report.conflicts.push(format!("account '{}': {} {} vs {}, kept {}", ours.name, field, a, b, a));
+                }
+            }
+        }
+
+        let before = serde_json::to_value(&*ours).ok();
+        fill(&mut ours.target_server, &theirs.target_server);
+        fill(&mut ours.user_id, &theirs.user_id);
+        fill(&mut ours.username, &theirs.username);
+        fill(&mut ours.discord_nickname, &theirs.discord_nickname);
+        fill(&mut ours.potion_slot, &theirs.potion_slot);
+        fill(&mut ours.refill_count, &theirs.refill_count);
+        fill(&mut ours.max_refills, &theirs.max_refills);
+        fill(&mut ours.spend_on_events, &theirs.spend_on_events);
+        fill(&mut ours.next_mode, &theirs.next_mode);
+        if theirs.last_run.is_some() && theirs.last_run > ours.last_run {
+            ours.last_run = theirs.last_run.clone();
+            ours.status = theirs.status.clone();
+        }
+        ours.ping_enabled |= theirs.ping_enabled;
+        ours.handout_enabled |= theirs.handout_enabled;
+        if serde_json::to_value(&*ours).ok() != before {
+            report.updated.push(ours.name.clone());
+        }
+    }
+
+    let base_cookies: Vec<Option<String>> = base.settings.session_cookies.iter().map(|c| base_key.decrypt(&c.value).ok()).collect();
+    for theirs in &other.settings.session_cookies {
+        let value = match other_key.decrypt(&theirs.value) {
+            Ok(v) => v,
+            Err(e) => {
+                report.skipped.push(format!("cookie '{}': {}", theirs.label, e));
+                continue;
+            },
+        };
+        if base_cookies.iter().any(|c| c.as_deref() == Some(value.as_str())) {
+            continue;
+        }
+        if base.settings.session_cookies.iter().any(|c| c.label == theirs.label) {
+            report.conflicts.push(format!("cookie '{}': different values, kept the base one", theirs.label));
+            continue;
+        }
+        base.settings.session_cookies.push(SessionCookie { value: base_key.encrypt(&value), ..theirs.clone() });
+        report.cookies_added.push(theirs.label.clone());
+    }
+
+    for admin in &other.settings.admins {
+        if !base.settings.admins.contains(admin) {
+            base.settings.admins.push(admin.clone());
+            report.admins_added += 1;
+        }
+    }
+    fill(&mut base.settings.admin_role_id, &other.settings.admin_role_id);
+    fill(&mut base.settings.log_channel_id, &other.settings.log_channel_id);
+
+    for run in &other.history {
+        if !base.history.iter().any(|r| r.account == run.account && r.started_at == run.started_at) {
+            base.history.push(run.clone());
+            report.history_added += 1;
+        }
+    }
+    base.history.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+    report
+}
+
+fn fill<T: Clone>(ours: &mut Option<T>, theirs: &Option<T>) {
+    if ours.is_none() {
+        ours.clone_from(theirs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(json: Value) -> DbData {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn account(name: &str, code: &str, cipher: &Cipher) -> Value {
+        serde_json::json!({"name": name, "code": cipher.encrypt(code), "pingEnabled": false, "status": "pending"})
+    }
+
+    #[test]
+    fn validate_reports_schema_problems() {
+        let key = Cipher::new("k");
+        let raw = serde_json::json!({
+            "accounts": [
+                {"name": "Nyx", "code": "PLAIN", "pingEnabled": false, "status": "idle", "encryptedCode": "U2Fsd"},
+                account("nyx", "C2", &key),
+                account("nyx", "C3", &key)
+            ],
+            "settings": {
+                "sessionCookies": [{"label": "main", "value": Cipher::new("other").encrypt("s")}],
+                "schedules": [{"name": "daily_run", "cron": "0 25 * * *", "timezone": "Mars/Olympus", "enabled": true}]
+            }
+        }).to_string();
+        let findings: Vec<String> = validate_json(&raw, Some(&key)).iter().map(|f| f.to_string()).collect();
+        let has = |s: &str| findings.iter().any(|f| f.contains(s));
+        assert!(has("[WARN] accounts[0].encryptedCode: unknown field"), "{:#?}", findings);
+        assert!(has("[WARN] account 'Nyx': restore code is stored in plaintext"));
+        assert!(has("[WARN] account 'nyx': differs from 'Nyx' only in case"));
+        assert!(has("[ERROR] account 'nyx': duplicate name"));
+        assert!(has("[ERROR] cookie 'main': value: encrypted with a different ENCRYPTION_KEY"));
+        assert!(has("invalid cron expression"));
+        assert!(has("unknown timezone 'Mars/Olympus'"));
+        assert_eq!(findings.len(), 7, "{:#?}", findings);
+
+        assert!(validate_json("{\"accounts\": []}", None)[0].message.contains("schema"));
+    }
+
+    #[test]
+    fn merge_fills_gaps_and_reports_conflicts() {
+        let ours_key = Cipher::new("ours");
+        let theirs_key = Cipher::new("theirs");
+        let mut base = data(serde_json::json!({
+            "accounts": [account("Geats", "G1", &ours_key), account("Axel", "A1", &ours_key)],
+            "settings": {"sessionCookies": [{"label": "main", "value": ours_key.encrypt("s1")}], "admins": ["1"]}
+        }));
+        let mut axel = account("axel", "A2", &theirs_key);
+        axel["userId"] = "9".into();
+        let mut geats = account("geats-alt", "G1", &theirs_key);
+        geats["targetServer"] = "E-26".into();
+        geats["lastRun"] = "2025-12-20T22:19:05Z".into();
+        geats["status"] = "done".into();
+        let other = data(serde_json::json!({
+            "accounts": [geats, axel, account("Muzan", "M1", &theirs_key), {"name": "Bad", "code": "v1:AAAA", "pingEnabled": false, "status": "x"}],
+            "settings": {
+                "sessionCookies": [
+                    {"label": "main", "value": theirs_key.encrypt("s2")},
+                    {"label": "spare", "value": theirs_key.encrypt("s1")},
+                    {"label": "extra", "value": theirs_key.encrypt("s3")}
+                ],
+                "admins": ["1", "2"]
+            }
+        }));
+
+        let report = merge(&mut base, &other, &ours_key, &theirs_key);
+        assert_eq!(report.added, ["Muzan"]);
+        assert_eq!(report.updated, ["Geats"]);
+        assert_eq!(report.conflicts.len(), 3, "{:#?}", report.conflicts);
+        assert!(report.conflicts[0].contains("same restore code as 'Geats'"));
+        assert!(report.conflicts[1].contains("'Axel': different restore codes"));
+        assert!(report.conflicts[2].contains("cookie 'main'"));
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.cookies_added, ["extra"]);
+        assert_eq!(report.admins_added, 1);
+
+        let geats = &base.accounts[0];
+        assert_eq!(geats.target_server.as_deref(), Some("E-26"));
+        assert_eq!(geats.status, "done");
+        assert!(base.accounts[1].user_id.is_none(), "conflicting account is left alone");
+        assert_eq!(ours_key.decrypt(&base.accounts[2].code).unwrap(), "M1");
+        assert_eq!(ours_key.decrypt(&base.settings.session_cookies[1].value).unwrap(), "s3");
+    }
+}
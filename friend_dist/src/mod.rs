@@ -1,2 +0,0 @@
-pub mod socket;
-pub mod db;
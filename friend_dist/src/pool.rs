@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio_util::sync::CancellationToken;
+
+use crate::db::Account;
+use crate::protocol::socket::{ProgressHandle, RunMode, SessionProgress};
+
+/// Concurrency used when `Settings.maxConcurrentSessions` is not set.
+pub const DEFAULT_CONCURRENCY: usize = 1;
+
+struct Running {
+    cancel: CancellationToken,
+    progress: ProgressHandle,
+}
+
+#[derive(Default)]
+struct PoolState {
+    /// Accounts that currently have a live session.
+    running: HashMap<String, Running>,
+    /// Queue managers that are currently active, by key, with the token /force_stop_all
+    /// fires. Each manager gets a fresh token, so a stop only reaches the managers
+    /// running at that moment.
+    managers: HashMap<String, CancellationToken>,
+    /// Held by a cookie probe; no session may start meanwhile.
+    reserved: bool,
+}
+
+/// Shared bookkeeping for every queue manager and single-account run.
+/// Guarantees an account is never in two sessions at once and that the
+/// total number of sessions stays under the configured limit.
+#[derive(Default)]
+pub struct WorkerPool {
+    state: Mutex<PoolState>,
+}
+
+/// Why an account could not be claimed.
+#[derive(Debug, PartialEq)]
+pub enum ClaimError {
+    AlreadyRunning,
+    PoolFull,
+    /// A cookie probe holds the whole pool.
+    Reserved,
+}
+
+/// Held by a worker for the duration of a session. Releases the account on drop.
+pub struct Lease {
+    pool: Arc<WorkerPool>,
+    name: String,
+    cancel: CancellationToken,
+    progress: ProgressHandle,
+}
+
+impl Lease {
+    /// Fires when /stop or /force_stop_all wants this session to end.
+    pub fn cancel_token(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    /// Where the session reports its state for /queue_status.
+    pub fn progress(&self) -> ProgressHandle {
+        Arc::clone(&self.progress)
+    }
+}
+
+/// Held while a cookie probe runs. No session can be claimed until it is dropped.
+pub struct Reservation {
+    pool: Arc<WorkerPool>,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.pool.state.lock().unwrap().reserved = false;
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        let mut state = self.pool.state.lock().unwrap();
+        state.running.remove(&self.name);
+    }
+}
+
+impl WorkerPool {
+    /// Reserves `name` for one `mode` session, as long as fewer than `limit` are running.
+    pub fn try_claim(self: &Arc<Self>, name: &str, mode: RunMode, limit: usize) -> Result<Lease, ClaimError> {
+        let mut state = self.state.lock().unwrap();
+        if state.running.contains_key(name) {
+            return Err(ClaimError::AlreadyRunning);
+        }
+        if state.reserved {
+            return Err(ClaimError::Reserved);
+        }
+        if state.running.len() >= limit.max(1) {
+            return Err(ClaimError::PoolFull);
+        }
+        let cancel = CancellationToken::new();
+        let progress = ProgressHandle::default();
+        progress.lock().unwrap().mode = Some(mode);
+        state.running.insert(name.to_string(), Running { cancel: cancel.clone(), progress: Arc::clone(&progress) });
+        Ok(Lease { pool: Arc::clone(self), name: name.to_string(), cancel, progress })
+    }
+
+    /// Takes the whole pool for a cookie probe, which would stop any game the cookie is
+    /// playing. Returns None while sessions are running or another probe holds the pool.
+    pub fn reserve_all(self: &Arc<Self>) -> Option<Reservation> {
+        let mut state = self.state.lock().unwrap();
+        if state.reserved || !state.running.is_empty() {
+            return None;
+        }
+        state.reserved = true;
+        Some(Reservation { pool: Arc::clone(self) })
+    }
+
+    /// Snapshot of every live session, by account name.
+    pub fn sessions(&self) -> Vec<(String, SessionProgress)> {
+        let state = self.state.lock().unwrap();
+        let mut sessions: Vec<_> = state.running.iter()
+            .map(|(name, running)| (name.clone(), running.progress.lock().unwrap().clone()))
+            .collect();
+        sessions.sort_by_key(|s| s.1.started);
+        sessions
+    }
+
+    /// Cancels the live session of one account. Returns the mode of the cancelled
+    /// session, or None if the account isn't running.
+    pub fn cancel(&self, name: &str) -> Option<RunMode> {
+        let state = self.state.lock().unwrap();
+        let running = state.running.get(name)?;
+        running.cancel.cancel();
+        let mode = running.progress.lock().unwrap().mode;
+        mode
+    }
+
+    /// Registers a queue manager (or a single force run). Returns the token that fires when
+    /// /force_stop_all halts it, or None if one with the same key is already active.
+    pub fn start_manager(&self, key: &str) -> Option<CancellationToken> {
+        let mut state = self.state.lock().unwrap();
+        if state.managers.contains_key(key) {
+            return None;
+        }
+        let stop = CancellationToken::new();
+        state.managers.insert(key.to_string(), stop.clone());
+        Some(stop)
+    }
+
+    pub fn finish_manager(&self, key: &str) {
+        self.state.lock().unwrap().managers.remove(key);
+    }
+
+    /// Halts every active queue manager and cancels all live sessions.
+    pub fn stop(&self) {
+        let state = self.state.lock().unwrap();
+        for stop in state.managers.values() {
+            stop.cancel();
+        }
+        for running in state.running.values() {
+            running.cancel.cancel();
+        }
+    }
+}
+
+/// Orders accounts so that every user gets a turn before anyone gets a second one.
+/// Pending accounts come before error/retrying ones; within each group users are
+/// interleaved in order of their first appearance.
+pub fn fair_order(accounts: Vec<Account>) -> Vec<Account> {
+    let (pending, errors): (Vec<Account>, Vec<Account>) = accounts.into_iter()
+        .partition(|a| !a.status.starts_with("error"));
+
+    let mut ordered = interleave_by_user(pending);
+    ordered.extend(interleave_by_user(errors));
+    ordered
+}
+
+fn interleave_by_user(accounts: Vec<Account>) -> Vec<Account> {
+    let mut users: Vec<Option<String>> = Vec::new();
+    let mut buckets: HashMap<Option<String>, std::collections::VecDeque<Account>> = HashMap::new();
+    for acc in accounts {
+        let key = acc.user_id.clone();
+        if !buckets.contains_key(&key) {
+            users.push(key.clone());
+        }
+        buckets.entry(key).or_default().push_back(acc);
+    }
+
+    let mut ordered = Vec::new();
+    loop {
+        let mut progressed = false;
+        for user in &users {
+            if let Some(acc) = buckets.get_mut(user).and_then(|b| b.pop_front()) {
+                ordered.push(acc);
+                progressed = true;
+            }
+        }
+        if !progressed {
+            break;
+        }
+    }
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(name: &str, user: &str, status: &str) -> Account {
+        Account { user_id: Some(user.to_string()), status: status.to_string(), ..Account::new(name, String::new()) }
+    }
+
+    fn names(accounts: &[Account]) -> Vec<&str> {
+        accounts.iter().map(|a| a.name.as_str()).collect()
+    }
+
+    #[test]
+    fn claims_are_exclusive_and_limited() {
+        let pool = Arc::new(WorkerPool::default());
+        let a = pool.try_claim("A", RunMode::Daily, 2).unwrap();
+        assert_eq!(pool.try_claim("A", RunMode::Daily, 2).err(), Some(ClaimError::AlreadyRunning));
+        let _b = pool.try_claim("B", RunMode::Daily, 2).unwrap();
+        assert_eq!(pool.try_claim("C", RunMode::Daily, 2).err(), Some(ClaimError::PoolFull));
+
+        drop(a);
+        assert!(pool.try_claim("C", RunMode::Daily, 2).is_ok(), "dropping a lease frees its slot");
+        // A limit of 0 still allows one session
+        assert!(Arc::new(WorkerPool::default()).try_claim("A", RunMode::Daily, 0).is_ok());
+    }
+
+    #[test]
+    fn cancel_and_stop_reach_live_sessions() {
+        let pool = Arc::new(WorkerPool::default());
+        let a = pool.try_claim("A", RunMode::Daily, 3).unwrap();
+        let b = pool.try_claim("B", RunMode::Handout, 3).unwrap();
+        assert_eq!(pool.cancel("nobody"), None);
+        assert_eq!(pool.cancel("A"), Some(RunMode::Daily));
+        assert!(a.cancel_token().is_cancelled());
+        assert!(!b.cancel_token().is_cancelled());
+
+        let draining = pool.start_manager("daily:all").unwrap();
+        assert!(pool.start_manager("daily:all").is_none(), "one manager per key");
+        pool.stop();
+        assert!(b.cancel_token().is_cancelled());
+        assert!(draining.is_cancelled());
+
+        // A run started while the stopped manager is still draining isn't stopped with it
+        let fresh = pool.start_manager("handout").unwrap();
+        assert!(!fresh.is_cancelled());
+        assert!(draining.is_cancelled());
+        pool.finish_manager("daily:all");
+        assert!(pool.start_manager("daily:all").is_some_and(|stop| !stop.is_cancelled()));
+    }
+
+    #[test]
+    fn reservation_holds_off_every_claim() {
+        let pool = Arc::new(WorkerPool::default());
+        let a = pool.try_claim("A", RunMode::Daily, 2).unwrap();
+        assert!(pool.reserve_all().is_none(), "not while a session runs");
+        drop(a);
+
+        let reservation = pool.reserve_all().unwrap();
+        assert!(pool.reserve_all().is_none(), "one probe at a time");
+        assert_eq!(pool.try_claim("A", RunMode::Daily, 2).err(), Some(ClaimError::Reserved));
+        drop(reservation);
+        assert!(pool.try_claim("A", RunMode::Daily, 2).is_ok());
+    }
+
+    #[test]
+    fn fair_order_interleaves_users_and_puts_errors_last() {
+        let ordered = fair_order(vec![
+            account("a1", "alice", "pending"),
+            account("a2", "alice", "pending"),
+            account("a3", "alice", "error: ZIGZA_DETECTED Retrying (1/3)"),
+            account("b1", "bob", "error: SERVER_FULL Retrying (1/3)"),
+            account("b2", "bob", "pending"),
+            account("c1", "carol", "pending"),
+        ]);
+        assert_eq!(names(&ordered), ["a1", "b2", "c1", "a2", "a3", "b1"]);
+    }
+}
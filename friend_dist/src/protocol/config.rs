@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_WS_URL: &str = "wss://evertext.sytes.net/socket.io/?EIO=4&transport=websocket";
+const DEFAULT_HTTP_URL: &str = "https://evertext.sytes.net/";
+pub const DEFAULT_TRANSCRIPT_DIR: &str = "transcripts";
+const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+
+/// Where and how `EvertextClient` connects.
+///
+/// Resolved as: built-in defaults, then the `client` block in `Settings`
+/// (any subset of fields), then `EVERTEXT_*` environment variables.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientConfig {
+    #[serde(rename = "wsUrl")]
+    pub ws_url: String,
+    #[serde(rename = "httpUrl")]
+    pub http_url: String,
+    /// Origin header. Derived from `http_url` when unset.
+    pub origin: Option<String>,
+    /// Host header override. When unset no header is set here and the WebSocket library
+    /// sends the host of `ws_url`.
+    pub host: Option<String>,
+    #[serde(rename = "userAgent")]
+    pub user_agent: String,
+    /// How long to wait for the Engine.IO open packet.
+    #[serde(rename = "handshakeTimeoutSecs")]
+    pub handshake_timeout_secs: u64,
+    /// Use this instead of the pingInterval the server announces.
+    #[serde(rename = "pingIntervalMs")]
+    pub ping_interval_ms: Option<u64>,
+    /// Give up when the game prints nothing for this long.
+    #[serde(rename = "activityTimeoutSecs")]
+    pub activity_timeout_secs: u64,
+    /// Prompt -> response rules file (.toml or .json). Built-in rules when unset.
+    #[serde(rename = "rulesPath")]
+    pub rules_path: Option<String>,
+    /// Where per-run terminal transcripts are written. Empty disables them.
+    #[serde(rename = "transcriptDir")]
+    pub transcript_dir: String,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            ws_url: DEFAULT_WS_URL.to_string(),
+            http_url: DEFAULT_HTTP_URL.to_string(),
+            origin: None,
+            host: None,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            handshake_timeout_secs: 15,
+            ping_interval_ms: None,
+            activity_timeout_secs: 120,
+            rules_path: None,
+            transcript_dir: DEFAULT_TRANSCRIPT_DIR.to_string(),
+        }
+    }
+}
+
+impl ClientConfig {
+    /// Settings block (if any) with environment overrides applied on top.
+    pub fn resolve(settings: Option<&ClientConfig>) -> Self {
+        let mut config = settings.cloned().unwrap_or_default();
+
+        if let Ok(v) = std::env::var("EVERTEXT_WS_URL") { config.ws_url = v; }
+        if let Ok(v) = std::env::var("EVERTEXT_HTTP_URL") { config.http_url = v; }
+        if let Ok(v) = std::env::var("EVERTEXT_ORIGIN") { config.origin = Some(v); }
+        if let Ok(v) = std::env::var("EVERTEXT_HOST") { config.host = Some(v); }
+        if let Ok(v) = std::env::var("EVERTEXT_USER_AGENT") { config.user_agent = v; }
+        if let Some(v) = env_u64("EVERTEXT_HANDSHAKE_TIMEOUT_SECS") { config.handshake_timeout_secs = v; }
+        if let Some(v) = env_u64("EVERTEXT_PING_INTERVAL_MS") { config.ping_interval_ms = Some(v); }
+        if let Some(v) = env_u64("EVERTEXT_ACTIVITY_TIMEOUT_SECS") { config.activity_timeout_secs = v; }
+        if let Ok(v) = std::env::var("EVERTEXT_RULES_PATH") { config.rules_path = Some(v); }
+        if let Ok(v) = std::env::var("EVERTEXT_TRANSCRIPT_DIR") { config.transcript_dir = v; }
+
+        config
+    }
+
+    pub fn origin(&self) -> Option<String> {
+        if let Some(origin) = &self.origin {
+            return Some(origin.clone());
+        }
+        let url = url::Url::parse(&self.http_url).ok()?;
+        Some(url.origin().ascii_serialization())
+    }
+}
+
+fn env_u64(name: &str) -> Option<u64> {
+    let raw = std::env::var(name).ok()?;
+    match raw.parse() {
+        Ok(v) => Some(v),
+        Err(_) => {
+            println!("[WARN] Ignoring {}='{}': not a number", name, raw);
+            None
+        }
+    }
+}
@@ -1 +1,7 @@
+pub mod config;
+pub mod rules;
 pub mod socket;
+pub mod transcript;
+
+#[cfg(any(test, feature = "test-util"))]
+pub mod stand_in;
@@ -0,0 +1,197 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+use super::socket::{GameState, RunMode, SessionErrorKind};
+
+const DEFAULT_RULES: &str = include_str!("default_rules.toml");
+
+/// What a rule does when it fires.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Action {
+    /// Type `text` into the terminal. `{code}` is replaced by the restore code.
+    Send { text: String },
+    /// Pick `account.target_server` out of the server list.
+    SelectServer,
+    /// Send nothing; the terminal carries on by itself.
+    Wait,
+    /// The run finished.
+    Complete,
+    /// The session failed with this error class.
+    Fail { error: SessionErrorKind },
+}
+
+/// One prompt -> response rule. See `default_rules.toml` for the field reference.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    #[serde(default)]
+    pub contains: Vec<String>,
+    #[serde(default)]
+    pub regex: Option<String>,
+    #[serde(default)]
+    pub mode: Option<RunMode>,
+    #[serde(default)]
+    pub once: bool,
+    #[serde(default)]
+    pub after: Option<String>,
+    #[serde(default)]
+    pub state: Option<GameState>,
+    #[serde(default)]
+    pub set_state: Option<GameState>,
+    #[serde(default)]
+    pub target_server: Option<bool>,
+    pub action: Action,
+    #[serde(skip)]
+    compiled: Option<Regex>,
+}
+
+impl Rule {
+    fn matches(&self, text: &str) -> bool {
+        self.contains.iter().all(|needle| text.contains(needle.as_str()))
+            && self.compiled.as_ref().is_none_or(|re| re.is_match(text))
+    }
+
+    /// Whether the rule may fire, given how often each rule fired on earlier chunks.
+    fn allowed(&self, mode: RunMode, state: GameState, has_target: bool, fired_before: &HashMap<String, u32>) -> bool {
+        if self.mode.is_some_and(|m| m != mode) {
+            return false;
+        }
+        if self.target_server.is_some_and(|wanted| wanted != has_target) {
+            return false;
+        }
+        if self.once && fired_before.contains_key(&self.name) {
+            return false;
+        }
+        if let Some(after) = &self.after {
+            if !fired_before.contains_key(after) {
+                return false;
+            }
+        }
+        self.state.is_none_or(|s| s == state)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RulesFile {
+    #[serde(rename = "rule")]
+    rules: Vec<Rule>,
+}
+
+/// Ordered rule list used by `EvertextClient::handle_event`.
+#[derive(Debug, Clone)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    /// The rules compiled into the binary (the bot's original behaviour).
+    pub fn builtin() -> Self {
+        Self::parse(DEFAULT_RULES, "toml").expect("built-in rules must be valid")
+    }
+
+    /// Rules from `path` (.toml or .json), or the built-in set when no path is given.
+    /// A broken file is reported and the built-in set is used instead, so a typo
+    /// can't stall every account.
+    pub fn load(path: Option<&str>) -> Self {
+        let Some(path) = path else {
+            return Self::builtin();
+        };
+        let format = if path.ends_with(".json") { "json" } else { "toml" };
+        match std::fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|c| Self::parse(&c, format)) {
+            Ok(rules) => rules,
+            Err(e) => {
+                println!("[ERROR] Could not load rules from {}: {}. Using built-in rules.", path, e);
+                Self::builtin()
+            }
+        }
+    }
+
+    pub fn parse(content: &str, format: &str) -> Result<Self, String> {
+        let file: RulesFile = match format {
+            "json" => serde_json::from_str(content).map_err(|e| e.to_string())?,
+            _ => toml::from_str(content).map_err(|e| e.to_string())?,
+        };
+
+        let mut rules = file.rules;
+        let names: HashSet<String> = rules.iter().map(|r| r.name.clone()).collect();
+        for rule in rules.iter_mut() {
+            if rule.contains.is_empty() && rule.regex.is_none() {
+                return Err(format!("rule '{}' needs 'contains' or 'regex'", rule.name));
+            }
+            if let Some(after) = &rule.after {
+                if !names.contains(after) {
+                    return Err(format!("rule '{}' refers to unknown rule '{}'", rule.name, after));
+                }
+            }
+            if let Some(pattern) = &rule.regex {
+                rule.compiled = Some(Regex::new(pattern).map_err(|e| format!("rule '{}': {}", rule.name, e))?);
+            }
+        }
+        Ok(Self { rules })
+    }
+
+    /// Rules that fire for this chunk, in file order. `has_target` tells whether the account
+    /// has a `targetServer`.
+    pub fn matching<'a>(&'a self, text: &'a str, mode: RunMode, state: GameState, has_target: bool, fired_before: &'a HashMap<String, u32>) -> impl Iterator<Item = &'a Rule> + 'a {
+        self.rules.iter().filter(move |r| r.matches(text) && r.allowed(mode, state, has_target, fired_before))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fire(rules: &RuleSet, text: &str, mode: RunMode, fired: &mut HashMap<String, u32>) -> Vec<String> {
+        let before = fired.clone();
+        let names: Vec<String> = rules.matching(text, mode, GameState::Connected, false, &before).map(|r| r.name.clone()).collect();
+        for name in &names {
+            *fired.entry(name.clone()).or_insert(0) += 1;
+        }
+        names
+    }
+
+    #[test]
+    fn once_and_after_alternate_responses() {
+        let rules = RuleSet::builtin();
+        let mut fired = HashMap::new();
+        assert_eq!(fire(&rules, "next: Go to the next event", RunMode::Daily, &mut fired), ["next-event-auto"]);
+        assert_eq!(fire(&rules, "next: Go to the next event", RunMode::Daily, &mut fired), ["next-event-exit"]);
+        assert_eq!(fire(&rules, "next: Go to the next event", RunMode::Daily, &mut fired), ["next-event-exit"]);
+    }
+
+    #[test]
+    fn server_selection_only_changes_state_with_a_target() {
+        let rules = RuleSet::builtin();
+        let prompt = "Which acc u want to Login\n1--> Geats (E-1)\n2--> Geats (E-2)";
+        let fired = HashMap::new();
+
+        let without: Vec<&Rule> = rules.matching(prompt, RunMode::Daily, GameState::SentCode, false, &fired).collect();
+        assert_eq!(without.len(), 1);
+        assert!(matches!(without[0].action, Action::Wait), "nothing is sent without a targetServer");
+        assert_eq!(without[0].set_state, None, "the terminal picks the server; the state is left alone");
+
+        let with: Vec<&Rule> = rules.matching(prompt, RunMode::Daily, GameState::SentCode, true, &fired).collect();
+        assert_eq!(with.len(), 1);
+        assert!(matches!(with[0].action, Action::SelectServer));
+        assert_eq!(with[0].set_state, Some(GameState::ServerSelected));
+    }
+
+    #[test]
+    fn custom_json_rules_with_regex() {
+        let json = r#"{"rule": [{"name": "potion", "regex": "select potion \\d", "mode": "daily", "action": {"type": "send", "text": "2"}}]}"#;
+        let rules = RuleSet::parse(json, "json").expect("valid rules");
+        let mut fired = HashMap::new();
+        assert_eq!(fire(&rules, "please select potion 1-3", RunMode::Daily, &mut fired), ["potion"]);
+        assert!(fire(&rules, "please select potion 1-3", RunMode::Handout, &mut fired).is_empty());
+    }
+
+    #[test]
+    fn rejects_dangling_after_and_empty_match() {
+        let dangling = "[[rule]]\nname = \"a\"\ncontains = [\"x\"]\nafter = \"missing\"\naction = { type = \"complete\" }\n";
+        assert!(RuleSet::parse(dangling, "toml").is_err());
+        let empty = "[[rule]]\nname = \"a\"\naction = { type = \"complete\" }\n";
+        assert!(RuleSet::parse(empty, "toml").is_err());
+    }
+}
@@ -1,26 +1,45 @@
 use futures_util::{SinkExt, StreamExt, stream::{SplitSink, SplitStream}};
+use serde::Deserialize;
 use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
+use reqwest::cookie::{CookieStore, Jar};
 use tokio::net::TcpStream;
+use tokio_util::sync::CancellationToken;
 use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
 use tokio_tungstenite::tungstenite::client::IntoClientRequest;
 use tokio_tungstenite::tungstenite::http::HeaderValue;
 use tokio_tungstenite::tungstenite::Message;
 use regex::Regex;
 
-use crate::db::Account; // Import Account struct
-
-const BASE_URL: &str = "wss://evertext.sytes.net/socket.io/?EIO=4&transport=websocket";
-const HTTP_URL: &str = "https://evertext.sytes.net/";
+use crate::db::{Account, NextMode}; // Import Account struct
+use super::config::ClientConfig;
+use super::rules::{Action, RuleSet};
+use super::transcript::Transcript;
+
+/// What the HTTP refresh got back.
+#[derive(Debug)]
+pub struct HttpRefresh {
+    pub status: reqwest::StatusCode,
+    /// New `session` value, when the server rotated the cookie.
+    pub rotated: Option<String>,
+}
 
-async fn do_http_refresh(cookie: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+/// GETs the site with the session cookie to wake the session up. The request goes
+/// through a cookie jar, so a `session` cookie re-issued by the server (including
+/// across redirects) is picked up and returned in `rotated`.
+pub async fn do_http_refresh(config: &ClientConfig, cookie: &str) -> Result<HttpRefresh, Box<dyn std::error::Error + Send + Sync>> {
     println!("[INFO] Refreshing session cookie via HTTP...");
+    let url = reqwest::Url::parse(&config.http_url)?;
+    let jar = Arc::new(Jar::default());
+    jar.add_cookie_str(&format!("session={}", cookie), &url);
     let client = reqwest::Client::builder()
-        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
+        .user_agent(config.user_agent.as_str())
+        .cookie_provider(Arc::clone(&jar))
         .build()?;
     
-    let res = client.get(HTTP_URL)
-        .header("Cookie", format!("session={}", cookie))
+    let res = client.get(url.clone())
         .send()
         .await?;
 
@@ -29,26 +48,230 @@ async fn do_http_refresh(cookie: &str) -> Result<(), Box<dyn std::error::Error +
     } else {
          println!("[WARN] HTTP Refresh returned status: {}", res.status());
     }
-    Ok(())
+
+    let rotated = jar.cookies(&url)
+        .and_then(|header| header.to_str().ok().map(str::to_string))
+        .and_then(|header| header.split(';').find_map(|pair| pair.trim().strip_prefix("session=").map(str::to_string)))
+        .filter(|value| !value.is_empty() && value != cookie);
+    if rotated.is_some() {
+        println!("[INFO] Server issued a new session cookie.");
+    }
+    Ok(HttpRefresh { status: res.status(), rotated })
+}
+
+/// Result of `EvertextClient::probe`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CookieCheck {
+    /// The game got past the login wall and asked for a command.
+    Valid,
+    /// "Access to start bot is restricted only for logged in users"
+    LoginRequired,
+    /// The check could not reach a verdict (site down, handshake failed, no answer, ...).
+    Inconclusive(String),
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+/// Fills the placeholders of a rule reply from the account's automation profile.
+/// `prior_fires` is how often the rule fired before, for `{refill}`. Single pass, so a
+/// restore code that happens to contain `{potion}` is sent as it is.
+fn render_reply(text: &str, account: &Account, code: &str, prior_fires: u32) -> String {
+    let value = |name: &str| -> Option<String> {
+        Some(match name {
+            "code" => code.to_string(),
+            "potion" => account.potion().to_string(),
+            "refill_count" => account.refills().to_string(),
+            "refill" => if account.max_refills.is_none_or(|max| prior_fires < max) { "y" } else { "n" }.to_string(),
+            "spend_events" => if account.spends_on_events() { "y" } else { "n" }.to_string(),
+            "next" => match account.next() { NextMode::Auto => "auto", NextMode::Manual => "next" }.to_string(),
+            _ => return None,
+        })
+    };
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        match tail.find('}').and_then(|end| value(&tail[1..end]).map(|v| (end, v))) {
+            Some((end, v)) => {
+                out.push_str(&v);
+                rest = &tail[end + 1..];
+            },
+            None => {
+                out.push('{');
+                rest = &tail[1..];
+            },
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Which game command a session plays.
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum RunMode {
+    /// `d`: the daily run.
     Daily,
+    /// `ho`: the handout run.
     Handout,
 }
 
-#[allow(dead_code)]
+impl RunMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunMode::Daily => "daily",
+            RunMode::Handout => "handout",
+        }
+    }
+}
+
+/// Snapshot of where a session was when it ended.
+#[derive(Debug, Clone)]
+pub struct SessionContext {
+    pub account: String,
+    pub last_prompt: Option<String>,
+    pub elapsed: Duration,
+    /// Path of the run's transcript file, if one was written.
+    pub transcript: Option<String>,
+}
+
+/// Successful end of a session.
+#[derive(Debug)]
+pub enum SessionOutcome {
+    /// Reached "Press y to perform more commands" - the run finished.
+    Completed(SessionContext),
+}
+
+/// Failure classes a session can end with.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize)]
+pub enum SessionErrorKind {
+    /// "Invalid Command ... Exiting Now" - safe to restart immediately.
+    InvalidCommand,
+    /// "Either Zigza error or Incorrect Restore Code Entered"
+    Zigza,
+    /// "Server reached maximum limit of restore accounts"
+    ServerFull,
+    /// Session cookie expired or missing.
+    LoginRequired,
+    /// Server sent 'idle_timeout'.
+    IdleTimeout,
+    /// Server sent 'connection_failed'.
+    ConnectionFailed,
+    /// Server sent 'disconnect'.
+    ServerDisconnect,
+    /// No Socket.IO ping from the server within the agreed interval.
+    HeartbeatTimeout,
+    /// No game output for too long.
+    ActivityTimeout,
+    /// Our keep-alive ping could not be written.
+    PingFailed,
+    /// WebSocket closed by the remote end.
+    SocketClosed,
+    /// Any other WebSocket / serialization error.
+    Transport,
+    /// Stopped by /stop or /force_stop_all.
+    Cancelled,
+}
+
+impl SessionErrorKind {
+    /// Legacy code string, kept for status fields and log lines.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SessionErrorKind::InvalidCommand => "INVALID_COMMAND_RESTART",
+            SessionErrorKind::Zigza => "ZIGZA_DETECTED",
+            SessionErrorKind::ServerFull => "SERVER_FULL",
+            SessionErrorKind::LoginRequired => "LOGIN_REQUIRED",
+            SessionErrorKind::IdleTimeout => "IDLE_TIMEOUT",
+            SessionErrorKind::ConnectionFailed => "CONNECTION_FAILED",
+            SessionErrorKind::ServerDisconnect => "SERVER_DISCONNECT",
+            SessionErrorKind::HeartbeatTimeout => "CONNECTION_TIMEOUT",
+            SessionErrorKind::ActivityTimeout => "ACTIVITY_TIMEOUT",
+            SessionErrorKind::PingFailed => "PING_FAILED",
+            SessionErrorKind::SocketClosed => "SOCKET_CLOSED",
+            SessionErrorKind::Transport => "TRANSPORT_ERROR",
+            SessionErrorKind::Cancelled => "CANCELLED",
+        }
+    }
+}
+
+impl std::fmt::Display for SessionErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// A session that ended before the run completed.
+#[derive(Debug)]
+pub struct SessionError {
+    pub kind: SessionErrorKind,
+    pub context: SessionContext,
+    /// Underlying transport error message, if any.
+    pub detail: Option<String>,
+}
+
+impl std::fmt::Display for SessionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(detail) = &self.detail {
+            write!(f, ": {}", detail)?;
+        }
+        write!(f, " (after {}s", self.context.elapsed.as_secs())?;
+        if let Some(prompt) = &self.context.last_prompt {
+            write!(f, ", last prompt: \"{}\"", prompt)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+/// How `EvertextClient::run_loop` ended.
+pub type SessionResult = Result<SessionOutcome, SessionError>;
+
+/// Live view of a session for status displays, updated as the game prints.
+#[derive(Debug, Clone)]
+pub struct SessionProgress {
+    pub mode: Option<RunMode>,
+    /// None until the game sends its first output.
+    pub state: Option<GameState>,
+    pub last_prompt: Option<String>,
+    pub started: chrono::DateTime<chrono::Utc>,
+}
+
+impl Default for SessionProgress {
+    fn default() -> Self {
+        Self { mode: None, state: None, last_prompt: None, started: chrono::Utc::now() }
+    }
+}
+
+pub type ProgressHandle = Arc<std::sync::Mutex<SessionProgress>>;
+
+/// What `handle_event` wants the loop to do next.
+enum Flow {
+    Continue,
+    Complete,
+    Fail(SessionErrorKind),
+}
+
+/// Upper bound on how long `probe` waits for the game's first prompt.
+const PROBE_TIMEOUT_SECS: u64 = 60;
+
+/// One Socket.IO connection to the EverText terminal. `connect`, then `run_loop` once.
 pub struct EvertextClient {
     write: SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
     read: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
     ping_interval: u64,
+    activity_timeout: u64,
     history: String,
+    last_prompt: Option<String>,
+    rules: Arc<RuleSet>,
+    transcript_dir: String,
+    transcript: Transcript,
+    progress: Option<ProgressHandle>,
+    rotated_cookie: Option<String>,
 }
 
-#[allow(dead_code)]
-#[derive(Debug, PartialEq)]
-enum GameState {
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize)]
+pub enum GameState {
     Connected,
     WaitingForCommandPrompt,
     SentD,
@@ -62,25 +285,112 @@ enum GameState {
 }
 
 impl EvertextClient {
-    pub async fn connect(cookie: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+    /// Refreshes the cookie over HTTP, then opens the socket. If the server rotated the
+    /// cookie, the new one is used for the socket and kept in `rotated_cookie` for the
+    /// caller to persist.
+    pub async fn connect(config: &ClientConfig, cookie: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
         // 0. Perform HTTP Refresh to wake up session
-        if let Err(e) = do_http_refresh(cookie).await {
-            println!("[WARN] HTTP Refresh failed: {}", e);
+        let rotated = match do_http_refresh(config, cookie).await {
+            Ok(refresh) => refresh.rotated,
+            Err(e) => {
+                println!("[WARN] HTTP Refresh failed: {}", e);
+                None
+            }
+        };
+        let mut client = Self::open(config, rotated.as_deref().unwrap_or(cookie)).await?;
+        client.rotated_cookie = rotated;
+        Ok(client)
+    }
+
+    /// The `session` cookie the server issued during `connect`, if it rotated it.
+    pub fn rotated_cookie(&self) -> Option<&str> {
+        self.rotated_cookie.as_deref()
+    }
+
+    /// Checks a session cookie without touching any account: HTTP refresh, Socket.IO
+    /// handshake, then `start` until the game either asks for a command or refuses
+    /// the cookie. The game is stopped again before a restore code is ever asked for.
+    /// Also returns the new cookie if the server rotated it during the refresh.
+    pub async fn probe(config: &ClientConfig, cookie: &str) -> (CookieCheck, Option<String>) {
+        let rotated = match do_http_refresh(config, cookie).await {
+            Ok(refresh) if !refresh.status.is_success() => {
+                return (CookieCheck::Inconclusive(format!("HTTP refresh returned {}", refresh.status)), refresh.rotated);
+            },
+            Ok(refresh) => refresh.rotated,
+            Err(e) => return (CookieCheck::Inconclusive(format!("HTTP refresh failed: {}", e)), None),
+        };
+        let mut client = match Self::open(config, rotated.as_deref().unwrap_or(cookie)).await {
+            Ok(client) => client,
+            Err(e) => return (CookieCheck::Inconclusive(format!("connection failed: {}", e)), rotated),
+        };
+        let wait = Duration::from_secs(client.activity_timeout.min(PROBE_TIMEOUT_SECS));
+        let verdict = match tokio::time::timeout(wait, client.await_verdict()).await {
+            Ok(verdict) => verdict,
+            Err(_) => CookieCheck::Inconclusive(format!("no answer from the game within {}s", wait.as_secs())),
+        };
+        let stop_payload = json!(["stop", {}]);
+        let _ = client.write.send(Message::Text(format!("42{}", stop_payload))).await;
+        let _ = client.write.close().await;
+        (verdict, rotated)
+    }
+
+    /// Starts the game and reads output until the rules tell whether we are logged in.
+    async fn await_verdict(&mut self) -> CookieCheck {
+        let fired = HashMap::new();
+        while let Some(msg) = self.read.next().await {
+            let text = match msg {
+                Ok(m) => m.to_string(),
+                Err(e) => return CookieCheck::Inconclusive(e.to_string()),
+            };
+            if text == "2" {
+                let _ = self.write.send(Message::Text("3".into())).await;
+            } else if text.starts_with("40") {
+                let start_payload = json!(["start", {"args": ""}]);
+                if let Err(e) = self.write.send(Message::Text(format!("42{}", start_payload))).await {
+                    return CookieCheck::Inconclusive(e.to_string());
+                }
+            } else if let Some(json_part) = text.strip_prefix("42") {
+                let Ok(event) = serde_json::from_str::<serde_json::Value>(json_part) else { continue };
+                if event.get(0).and_then(|n| n.as_str()) != Some("output") {
+                    continue;
+                }
+                let Some(output) = event.get(1).and_then(|d| d["data"].as_str()) else { continue };
+                let verdict = self.rules.matching(output, RunMode::Daily, GameState::Connected, false, &fired).next()
+                    .map(|rule| match &rule.action {
+                        Action::Fail { error: SessionErrorKind::LoginRequired } => CookieCheck::LoginRequired,
+                        Action::Fail { error } => CookieCheck::Inconclusive(format!("game answered {}", error)),
+                        _ => CookieCheck::Valid,
+                    });
+                if let Some(verdict) = verdict {
+                    return verdict;
+                }
+            }
         }
+        CookieCheck::Inconclusive("socket closed before the game answered".to_string())
+    }
 
-        let mut request = BASE_URL.into_client_request()?;
+    /// Socket.IO handshake and namespace join, without the HTTP refresh.
+    async fn open(config: &ClientConfig, cookie: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut request = config.ws_url.as_str().into_client_request()?;
         let headers = request.headers_mut();
         let cookie_header = format!("session={}", cookie);
         headers.insert("Cookie", HeaderValue::from_str(&cookie_header)?);
-        headers.insert("User-Agent", HeaderValue::from_static("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36"));
-        headers.insert("Origin", HeaderValue::from_static("https://evertext.sytes.net"));
-        headers.insert("Host", HeaderValue::from_static("evertext.sytes.net"));
+        headers.insert("User-Agent", HeaderValue::from_str(&config.user_agent)?);
+        if let Some(origin) = config.origin() {
+            headers.insert("Origin", HeaderValue::from_str(&origin)?);
+        }
+        if let Some(host) = &config.host {
+            headers.insert("Host", HeaderValue::from_str(host)?);
+        }
 
-        println!("[INFO] Connecting to EverText WebSocket...");
-        let (mut ws_stream, _) = connect_async(request).await?;
+        println!("[INFO] Connecting to EverText WebSocket at {}...", config.ws_url);
+        let handshake_timeout = Duration::from_secs(config.handshake_timeout_secs);
+        let (mut ws_stream, _) = tokio::time::timeout(handshake_timeout, connect_async(request))
+            .await
+            .map_err(|_| "Connection handshake timed out (Server likely starting up...)")??;
 
         // 1. Wait for "Open" packet (Type 0) with a timeout
-        let msg = tokio::time::timeout(Duration::from_secs(15), ws_stream.next())
+        let msg = tokio::time::timeout(handshake_timeout, ws_stream.next())
             .await
             .map_err(|_| "Connection handshake timed out (Server likely starting up...)")?
             .ok_or("Stream closed during handshake")??;
@@ -91,7 +401,8 @@ impl EvertextClient {
             let data: serde_json::Value = serde_json::from_str(json_part)?;
             
             let sid = data["sid"].as_str().ok_or("No SID found")?.to_string();
-            let ping = data["pingInterval"].as_u64().unwrap_or(25000);
+            let ping = config.ping_interval_ms
+                .unwrap_or_else(|| data["pingInterval"].as_u64().unwrap_or(25000));
             
             println!("[INFO] Connected! Session ID: {}", sid);
             
@@ -104,20 +415,77 @@ impl EvertextClient {
                 write,
                 read,
                 ping_interval: ping,
+                activity_timeout: config.activity_timeout_secs,
                 history: String::new(),
+                last_prompt: None,
+                rules: Arc::new(RuleSet::load(config.rules_path.as_deref())),
+                transcript_dir: config.transcript_dir.clone(),
+                transcript: Transcript::disabled(),
+                progress: None,
+                rotated_cookie: None,
             });
         }
 
         Err("Failed to handshake - unexpected server response".into())
     }
 
-    pub async fn run_loop(&mut self, account: &Account, decrypted_code: &str, mode: RunMode) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    /// Publishes state and last prompt to `progress` while sessions run.
+    pub fn report_to(&mut self, progress: ProgressHandle) {
+        self.progress = Some(progress);
+    }
+
+    fn publish(&self, mode: RunMode, state: GameState) {
+        if let Some(progress) = &self.progress {
+            let mut progress = progress.lock().unwrap();
+            progress.mode = Some(mode);
+            progress.state = Some(state);
+            progress.last_prompt = self.last_prompt.clone();
+        }
+    }
+
+    /// Plays one session until it completes, fails, or `cancel` fires. On cancellation the
+    /// game is sent its `stop` event and the socket is closed before returning `Cancelled`.
+    pub async fn run_loop(&mut self, account: &Account, decrypted_code: &str, mode: RunMode, cancel: &CancellationToken) -> SessionResult {
+        let started = Instant::now();
+        if !self.transcript_dir.is_empty() {
+            self.transcript = Transcript::create(&self.transcript_dir, &account.name, mode.as_str(), chrono::Utc::now());
+            self.transcript.mask(decrypted_code);
+        }
+
+        match self.drive(account, decrypted_code, mode, cancel).await {
+            Ok(()) => {
+                self.transcript.event("session ended: completed");
+                Ok(SessionOutcome::Completed(self.context(account, started)))
+            },
+            Err((kind, detail)) => {
+                match &detail {
+                    Some(detail) => self.transcript.event(&format!("session ended: {} ({})", kind, detail)),
+                    None => self.transcript.event(&format!("session ended: {}", kind)),
+                }
+                Err(SessionError {
+                    kind,
+                    context: self.context(account, started),
+                    detail,
+                })
+            },
+        }
+    }
+
+    fn context(&self, account: &Account, started: Instant) -> SessionContext {
+        SessionContext {
+            account: account.name.clone(),
+            last_prompt: self.last_prompt.clone(),
+            elapsed: started.elapsed(),
+            transcript: self.transcript.path(),
+        }
+    }
+
+    async fn drive(&mut self, account: &Account, decrypted_code: &str, mode: RunMode, cancel: &CancellationToken) -> Result<(), (SessionErrorKind, Option<String>)> {
         let mut last_ping = Instant::now();
         let mut state = GameState::Connected;
         
-        // Trackers
-        let mut auto_sent = false;
-        let mut handout_sent = false;
+        // How often each rule has fired so far (for 'once' / 'after' guards and {refill})
+        let mut fired: HashMap<String, u32> = HashMap::new();
 
         println!("[INFO][PID:{}] Starting session for account: {} (Mode: {:?})", std::process::id(), account.name, mode);
 
@@ -125,27 +493,36 @@ impl EvertextClient {
         let mut ping_timer = tokio::time::interval(Duration::from_secs(20)); // Keep-Alive for AWS
         let mut last_activity = Instant::now(); // Track game output activity
 
+        let transport = |e: Box<dyn std::error::Error + Send + Sync>| (SessionErrorKind::Transport, Some(e.to_string()));
+
         loop {
             tokio::select! {
+                _ = cancel.cancelled() => {
+                    println!("[INFO] Session for {} cancelled. Stopping the game...", account.name);
+                    let stop_payload = json!(["stop", {}]);
+                    let _ = self.write.send(Message::Text(format!("42{}", stop_payload))).await;
+                    let _ = self.write.close().await;
+                    return Err((SessionErrorKind::Cancelled, None));
+                }
                 _ = ping_timer.tick() => {
                     // Send Keep-Alive Ping (Anti-AWS Timeout)
                     // We don't log this to keep console clean, but it keeps the TCP connection alive
                     if (self.write.send(Message::Ping(vec![])).await).is_err() {
-                        return Err("PING_FAILED".into());
+                        return Err((SessionErrorKind::PingFailed, None));
                     }
                 }
                 _ = heartbeat_check.tick() => {
                      // 1. Connection Heartbeat (Ping/Pong)
                      if last_ping.elapsed().as_millis() as u64 > (self.ping_interval + 15000) {
                          println!("[ERROR] Connection timed out (no heartbeat from server). Last ping: {} ms ago", last_ping.elapsed().as_millis());
-                         return Err("CONNECTION_TIMEOUT".into());
+                         return Err((SessionErrorKind::HeartbeatTimeout, None));
                      }
 
                      // 2. Game Activity Timeout (Stuck on 'start' or unresponsive script)
-                     // If we haven't received any 'output' from the game in 120 seconds, assume stuck.
-                     if last_activity.elapsed().as_secs() > 120 {
-                         println!("[ERROR] Game Activity timed out (stuck for 120s). Disconnecting...");
-                         return Err("ACTIVITY_TIMEOUT".into());
+                     // If we haven't received any 'output' from the game for activity_timeout seconds, assume stuck.
+                     if last_activity.elapsed().as_secs() > self.activity_timeout {
+                         println!("[ERROR] Game Activity timed out (stuck for {}s). Disconnecting...", self.activity_timeout);
+                         return Err((SessionErrorKind::ActivityTimeout, None));
                      }
                 }
                 msg = self.read.next() => {
@@ -154,57 +531,95 @@ impl EvertextClient {
                             let text = m.to_string();
                             
                             if text == "2" {
-                                self.write.send(Message::Text("3".into())).await?;
+                                self.write.send(Message::Text("3".into())).await.map_err(|e| transport(e.into()))?;
                                 last_ping = Instant::now();
                             } else if text.starts_with("40") {
                                 // ... (existing code)
                                 println!("[INFO] Namespace joined. Initializing session...");
                                 let stop_payload = json!(["stop", {}]);
-                                self.write.send(Message::Text(format!("42{}", stop_payload))).await?;
+                                self.write.send(Message::Text(format!("42{}", stop_payload))).await.map_err(|e| transport(e.into()))?;
                                 tokio::time::sleep(Duration::from_millis(500)).await;
                                 println!("[ACTION] Sending 'start' event...");
+                                self.transcript.event("start");
                                 let start_payload = json!(["start", {"args": ""}]);
-                                self.write.send(Message::Text(format!("42{}", start_payload))).await?;
+                                self.write.send(Message::Text(format!("42{}", start_payload))).await.map_err(|e| transport(e.into()))?;
                                 last_activity = Instant::now(); // Reset activity on start
                             } else if text.starts_with("42") {
                                 // If we get actual game data, update activity
                                 if text.contains("output") {
                                     last_activity = Instant::now();
                                 }
-                                self.handle_event(&text, &mut state, account, decrypted_code, &mut auto_sent, &mut handout_sent, mode).await?;
+                                match self.handle_event(&text, &mut state, account, decrypted_code, &mut fired, mode).await.map_err(transport)? {
+                                    Flow::Continue => self.publish(mode, state),
+                                    Flow::Complete => return Ok(()),
+                                    Flow::Fail(kind) => return Err((kind, None)),
+                                }
                             }
                         }
-                        Some(Err(e)) => return Err(e.into()),
-                        None => return Err("Socket closed".into()),
+                        Some(Err(e)) => return Err(transport(e.into())),
+                        None => return Err((SessionErrorKind::SocketClosed, None)),
                     }
                 }
             }
         }
     }
 
+    /// Answers "Which acc u want to Login" with the index of `account.target_server`.
+    async fn select_server(&mut self, account: &Account) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(target) = &account.target_server {
+            println!("[ACTION] Prompt: 'Server Selection'. Parsing for '{}'...", target);
+            let mut selected_index = "1".to_string();
+            let re = Regex::new(r"(\d+)-->.*?\((.*?)\)").expect("Invalid regex pattern for server parsing");
+            let mut found = false;
+            
+            for cap in re.captures_iter(&self.history) {
+                let index = &cap[1];
+                let server_name = &cap[2];
+                if server_name.contains(target) || (target.to_lowercase() == "all" && server_name.contains("All of them")) {
+                    println!("[INFO] Found target server '{}' at index {}", target, index);
+                    selected_index = index.to_string();
+                    found = true;
+                    break;
+                }
+            }
+            if !found { println!("[WARN] Target '{}' not found. Defaulting to '1'.", target); }
+            
+            println!("[ACTION] Sending server choice: {}", selected_index);
+            self.send_command(&selected_index).await?;
+        } else {
+            println!("[INFO] No targetServer specified. Assuming single server - waiting for terminal to auto-select.");
+            // Do NOT send any command. Terminal handles it.
+        }
+        Ok(())
+    }
+
     async fn send_command(&mut self, cmd: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
          let payload = json!(["input", {"input": cmd}]); 
          let packet = format!("42{}", payload);
+         self.transcript.input(cmd);
          self.write.send(Message::Text(packet)).await?;
          Ok(())
     }
 
-    #[allow(clippy::too_many_arguments)]
-    async fn handle_event(&mut self, text: &str, state: &mut GameState, account: &Account, code: &str, auto_sent: &mut bool, handout_sent: &mut bool, mode: RunMode) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    async fn handle_event(&mut self, text: &str, state: &mut GameState, account: &Account, code: &str, fired: &mut HashMap<String, u32>, mode: RunMode) -> Result<Flow, Box<dyn std::error::Error + Send + Sync>> {
         let json_part = &text[2..];
         // Parse the event. If it fails, just ignore it (sometimes random packets come in)
         let event: serde_json::Value = match serde_json::from_str(json_part) {
             Ok(v) => v,
-            Err(_) => return Ok(()),
+            Err(_) => return Ok(Flow::Continue),
         };
         
         if let Some(event_array) = event.as_array() {
             let event_name = event_array.first().and_then(|v| v.as_str()).unwrap_or("");
             let event_data = event_array.get(1);
+            if event_name != "output" {
+                self.transcript.event(event_name);
+            }
 
             if event_name == "output" {
                  if let Some(data) = event_data {
                      if let Some(output_text) = data["data"].as_str() {
+                         self.transcript.output(output_text);
                          // Print terminal output (clean up newlines for log readability)
                          let clean_log = output_text.replace("\n", " ");
                          // Log only significant chunks to avoid spam
@@ -212,6 +627,11 @@ impl EvertextClient {
                              println!("[TERMINAL] {}", clean_log.chars().take(150).collect::<String>());
                          }
                          
+                        // Remember the last non-empty line for error context
+                        if let Some(line) = output_text.lines().rev().map(str::trim).find(|l| !l.is_empty()) {
+                            self.last_prompt = Some(line.chars().take(200).collect());
+                        }
+
                         // Update history for multi-line parsing
                         self.history.push_str(output_text);
                         if self.history.len() > 10000 {
@@ -222,168 +642,307 @@ impl EvertextClient {
                             self.history.replace_range(..drain_len, "");
                         }
 
-                         // --- 0. Error Handling (Must be First!) ---
-                         
-                         // "Invalid Command ... Exiting Now"
-                         if output_text.contains("Invalid Command") && output_text.contains("Exiting Now") {
-                             println!("[ERROR] Invalid Command Detected. Triggering Restart...");
-                             return Err("INVALID_COMMAND_RESTART".into());
-                         }
-
-                         if output_text.contains("Either Zigza error or Incorrect Restore Code Entered") {
-                             println!("[ERROR] Zigza Error Detected!");
-                             return Err("ZIGZA_DETECTED".into());
-                         }
-
-                         if output_text.contains("Server reached maximum limit of restore accounts") {
-                             println!("[ERROR] Server Full Detected!");
-                             return Err("SERVER_FULL".into());
-                         }
-
-                         if output_text.contains("Access to start bot is restricted only for logged in users") {
-                             println!("[ERROR] Login Required / Cookie Expired!");
-                             return Err("LOGIN_REQUIRED".into());
-                         }
-
-                         // --- 1. Initial / Login Flow ---
-                         if output_text.contains("Enter Command to use") {
-                             match mode {
-                                 RunMode::Daily => {
-                                     println!("[ACTION] Prompt: 'Enter Command'. Sending 'd' (Daily)...");
-                                     *state = GameState::SentD;
-                                     self.send_command("d").await?;
-                                 },
-                                 RunMode::Handout => {
-                                     println!("[ACTION] Prompt: 'Enter Command'. Sending 'ho' (Handout)...");
-                                     // State SentD is roughly equivalent to SentHo for flow purposes
-                                     *state = GameState::SentD; 
-                                     self.send_command("ho").await?;
-                                 }
-                             }
-                         }
-                         
-                         if output_text.contains("Enter Restore code") {
-                             println!("[ACTION] Prompt: 'Enter Restore code'. Sending Code...");
-                             *state = GameState::SentCode;
-                             self.send_command(code).await?;
-                         }
-
-                         // Server Selection
-                         if output_text.contains("Which acc u want to Login") {
-                             if let Some(target) = &account.target_server {
-                                 println!("[ACTION] Prompt: 'Server Selection'. Parsing for '{}'...", target);
-                                 let mut selected_index = "1".to_string();
-                                 let re = Regex::new(r"(\d+)-->.*?\((.*?)\)").expect("Invalid regex pattern for server parsing");
-                                 let mut found = false;
-                                 
-                                 for cap in re.captures_iter(&self.history) {
-                                     let index = &cap[1];
-                                     let server_name = &cap[2];
-                                     if server_name.contains(target) || (target.to_lowercase() == "all" && server_name.contains("All of them")) {
-                                         println!("[INFO] Found target server '{}' at index {}", target, index);
-                                         selected_index = index.to_string();
-                                         found = true;
-                                         break;
-                                     }
-                                 }
-                                 if !found { println!("[WARN] Target '{}' not found. Defaulting to '1'.", target); }
-                                 
-                                 println!("[ACTION] Sending server choice: {}", selected_index);
-                                 self.send_command(&selected_index).await?;
-                                 *state = GameState::ServerSelected;
-                             } else {
-                                 println!("[INFO] No targetServer specified. Assuming single server - waiting for terminal to auto-select.");
-                                 // Do NOT send any command. Terminal handles it.
+                         // --- Prompt -> response rules (see default_rules.toml) ---
+                         let fired_before = fired.clone();
+                         let rules = Arc::clone(&self.rules);
+                         for rule in rules.matching(output_text, mode, *state, account.target_server.is_some(), &fired_before) {
+                             let prior_fires = fired_before.get(&rule.name).copied().unwrap_or(0);
+                             *fired.entry(rule.name.clone()).or_insert(0) += 1;
+                             if let Some(next_state) = rule.set_state {
+                                 *state = next_state;
                              }
-                         }
-
-                         // --- 2. Main Game Flow ---
-                         
-                         // "Press y to spend mana on event stages :"
-                         if output_text.contains("Press y to spend mana on event stages") {
-                             match mode {
-                                 RunMode::Daily => {
-                                     println!("[ACTION] Prompt: 'Spend mana'. Sending 'y'...");
-                                     self.send_command("y").await?;
-                                 },
-                                 RunMode::Handout => {
-                                     if !*handout_sent {
-                                         println!("[ACTION] Prompt: 'Spend mana'. Sending 'ho' (Handout)...");
-                                         self.send_command("ho").await?;
-                                         *handout_sent = true;
+                             match &rule.action {
+                                 Action::Send { text } => {
+                                     let reply = render_reply(text, account, code, prior_fires);
+                                     if text.contains("{code}") {
+                                         println!("[ACTION] Rule '{}'. Sending restore code...", rule.name);
                                      } else {
-                                         println!("[ACTION] Prompt: 'Spend mana'. Sending 'y' (Handout Confirmation)...");
-                                         self.send_command("y").await?;
+                                         println!("[ACTION] Rule '{}'. Sending '{}'...", rule.name, reply);
                                      }
-                                 }
-                             }
-                         }
-
-                         // "next: Go to the next event. [default option if nothing entered]"
-                         if output_text.contains("next: Go to the next event") {
-                             if !*auto_sent {
-                                 println!("[ACTION] Prompt: 'next event'. Sending 'auto' (First time)...");
-                                 self.send_command("auto").await?;
-                                 *auto_sent = true;
-                             } else {
-                                 println!("[ACTION] Prompt: 'next event'. Sending 'exit' (Already sent auto)...");
-                                 self.send_command("exit").await?;
+                                     self.send_command(&reply).await?;
+                                 },
+                                 Action::SelectServer => self.select_server(account).await?,
+                                 Action::Wait => println!("[INFO] Rule '{}'. Waiting for the terminal.", rule.name),
+                                 Action::Complete => {
+                                     println!("[INFO] Rule '{}'. Run Complete.", rule.name);
+                                     return Ok(Flow::Complete); // Trigger clean exit
+                                 },
+                                 Action::Fail { error } => {
+                                     println!("[ERROR] Rule '{}' detected {}!", rule.name, error);
+                                     return Ok(Flow::Fail(*error));
+                                 },
                              }
                          }
-
-                         // --- 3. Mana Refill Logic (Situational) ---
-                         // "DO U WANT TO REFILL MANA ? (press y to refill):"
-                         // "DO U WANT TO REFILL MANA ? (press y to refill):"
-                         if output_text.contains("DO U WANT TO REFILL MANA") {
-                             println!("[ACTION] Prompt: 'Refill Mana'. Sending 'y'...");
-                             self.send_command("y").await?;
-                         }
-
-                         // "Enter 1, 2 or 3 to select potion to refill:"
-                         if output_text.contains("Enter 1, 2 or 3 to select potion to refill") {
-                             println!("[ACTION] Prompt: 'Select potion'. Sending '3'...");
-                             self.send_command("3").await?;
-                         }
-
-                         // "Enter the number of stam100 potions to refill"
-                         if output_text.contains("number of stam100 potions to refill") {
-                             println!("[ACTION] Prompt: 'Potion quantity'. Sending '1'...");
-                             self.send_command("1").await?;
-                         }
-
-                         // --- 4. More Events Prompt ---
-                         // "Press y to do more events:"
-                         // User logic: "we will write 'y' and now the terminal will ask for 'next: ...' now we will write 'exit'"
-                         if output_text.contains("Press y to do more events") {
-                             println!("[ACTION] Prompt: 'Do more events?'. Sending 'y' (waiting for 'next' prompt to exit)...");
-                             self.send_command("y").await?;
-                             // We do NOT send 'exit' here. We wait for the "next: Go to the next event" prompt to appear again.
-                             // Since 'auto_sent' is already true, the 'next' block above will handle sending 'exit'.
-                         }
-
-                         // --- 5. End of Loop ---
-                         // "Press y to perform more commands:"
-                         if output_text.contains("Press y to perform more commands") {
-                             println!("[INFO] Prompt: 'Perform more commands'. Run Complete.");
-                             return Err("SESSION_COMPLETE".into()); // Trigger clean exit
-                         }
-
-                         // --- Error Handling Moved to Top ---
                      }
                  }
             } else if event_name == "idle_timeout" {
                 println!("[ERROR] Server sent 'idle_timeout'. Disconnecting...");
-                return Err("IDLE_TIMEOUT".into());
+                return Ok(Flow::Fail(SessionErrorKind::IdleTimeout));
             } else if event_name == "connection_failed" {
                 println!("[ERROR] Server sent 'connection_failed'. Disconnecting...");
-                return Err("CONNECTION_FAILED".into());
+                return Ok(Flow::Fail(SessionErrorKind::ConnectionFailed));
             } else if event_name == "disconnect" {
                 println!("[ERROR] Server sent 'disconnect' event.");
-                return Err("SERVER_DISCONNECT".into());
+                return Ok(Flow::Fail(SessionErrorKind::ServerDisconnect));
             } else {
                 println!("[DEBUG] Unhandled Socket.io event: {} -> {:?}", event_name, event_data);
             }
         }
-        Ok(())
+        Ok(Flow::Continue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::stand_in::{Script, StandIn};
+
+    #[test]
+    fn reply_placeholders_are_filled_once() {
+        let mut acc = account(None);
+        acc.max_refills = Some(1);
+        assert_eq!(render_reply("{code}", &acc, "AB{potion}{refill}", 0), "AB{potion}{refill}");
+        assert_eq!(render_reply("{potion} {refill_count} {refill} {x} {", &acc, "", 1), "3 1 n {x} {");
+    }
+
+    fn account(target_server: Option<&str>) -> Account {
+        Account {
+            name: "TestAcc".to_string(),
+            code: "RESTORE123".to_string(),
+            target_server: target_server.map(str::to_string),
+            user_id: Some("1".to_string()),
+            username: None,
+            discord_nickname: None,
+            ping_enabled: false,
+            handout_enabled: false,
+            status: "pending".to_string(),
+            last_run: None,
+            potion_slot: None,
+            refill_count: None,
+            max_refills: None,
+            spend_on_events: None,
+            next_mode: None,
+            retry_counts: Default::default(),
+            next_attempt_at: None,
+        }
+    }
+
+    async fn run(script: Script, acc: &Account, mode: RunMode) -> (SessionResult, Vec<String>) {
+        let server = StandIn::start(script).await;
+        let result = {
+            let config = ClientConfig {
+                ws_url: server.ws_url.clone(),
+                http_url: server.http_url.clone(),
+                transcript_dir: String::new(),
+                ..ClientConfig::default()
+            };
+            let mut client = EvertextClient::connect(&config, "cookie").await.expect("connect");
+            client.run_loop(acc, "RESTORE123", mode, &CancellationToken::new()).await
+        };
+        (result, server.inputs().await)
+    }
+
+    #[tokio::test]
+    async fn daily_flow_completes() {
+        let script = Script::new()
+            .prompt("Enter Command to use")
+            .prompt("Enter Restore code")
+            .prompt("Which acc u want to Login\n1--> Alpha (E-1)\n2--> Beta (E-15)\n")
+            .prompt("Press y to spend mana on event stages :")
+            .prompt("next: Go to the next event. [default option if nothing entered]")
+            .prompt("DO U WANT TO REFILL MANA ? (press y to refill):")
+            .prompt("Enter 1, 2 or 3 to select potion to refill:")
+            .prompt("Enter the number of stam100 potions to refill")
+            .prompt("Press y to do more events:")
+            .prompt("next: Go to the next event. [default option if nothing entered]")
+            .output("Press y to perform more commands:");
+
+        let (result, inputs) = run(script, &account(Some("E-15")), RunMode::Daily).await;
+
+        assert!(matches!(result, Ok(SessionOutcome::Completed(_))), "{:?}", result);
+        assert_eq!(inputs, ["d", "RESTORE123", "2", "y", "auto", "y", "3", "1", "y", "exit"]);
+    }
+
+    #[tokio::test]
+    async fn automation_profile_changes_replies() {
+        let mut acc = account(None);
+        acc.potion_slot = Some(1);
+        acc.refill_count = Some(2);
+        acc.max_refills = Some(1);
+        acc.spend_on_events = Some(false);
+        acc.next_mode = Some(NextMode::Manual);
+
+        let script = Script::new()
+            .prompt("Press y to spend mana on event stages :")
+            .prompt("next: Go to the next event. [default option if nothing entered]")
+            .prompt("DO U WANT TO REFILL MANA ? (press y to refill):")
+            .prompt("Enter 1, 2 or 3 to select potion to refill:")
+            .prompt("Enter the number of stam100 potions to refill")
+            .prompt("DO U WANT TO REFILL MANA ? (press y to refill):")
+            .output("Press y to perform more commands:");
+
+        let (result, inputs) = run(script, &acc, RunMode::Daily).await;
+
+        assert!(matches!(result, Ok(SessionOutcome::Completed(_))), "{:?}", result);
+        assert_eq!(inputs, ["n", "next", "y", "1", "2", "n"]);
+    }
+
+    #[tokio::test]
+    async fn transcript_interleaves_output_and_masked_inputs() {
+        let dir = std::env::temp_dir().join(format!("evertext-transcript-test-{}", std::process::id()));
+        let script = Script::new()
+            .prompt("Enter Command to use")
+            .prompt("Enter Restore code")
+            .output("Either Zigza error or Incorrect Restore Code Entered");
+        let server = StandIn::start(script).await;
+        let config = ClientConfig {
+            ws_url: server.ws_url.clone(),
+            http_url: server.http_url.clone(),
+            transcript_dir: dir.to_string_lossy().into_owned(),
+            ..ClientConfig::default()
+        };
+        let mut client = EvertextClient::connect(&config, "cookie").await.expect("connect");
+        let result = client.run_loop(&account(None), "RESTORE123", RunMode::Daily, &CancellationToken::new()).await;
+        drop(client);
+        server.inputs().await;
+
+        let err = result.expect_err("zigza should fail the session");
+        let path = err.context.transcript.expect("transcript path");
+        let log = std::fs::read_to_string(&path).expect("transcript file");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let lines: Vec<&str> = log.lines().map(|l| l.split_once("] ").map_or(l, |(_, rest)| rest)).collect();
+        assert_eq!(lines, [
+            "-- start",
+            "<< Enter Command to use",
+            ">> d",
+            "<< Enter Restore code",
+            ">> <restore code>",
+            "<< Either Zigza error or Incorrect Restore Code Entered",
+            "-- session ended: ZIGZA_DETECTED",
+        ]);
+        assert!(path.ends_with("-daily-TestAcc.log"), "{}", path);
+    }
+
+    #[tokio::test]
+    async fn cancellation_stops_live_session() {
+        let script = Script::new()
+            .prompt("Enter Command to use")
+            .prompt("Something the rules don't answer");
+        let server = StandIn::start(script).await;
+        let config = ClientConfig {
+            ws_url: server.ws_url.clone(),
+            http_url: server.http_url.clone(),
+            transcript_dir: String::new(),
+            ..ClientConfig::default()
+        };
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(1500)).await;
+            trigger.cancel();
+        });
+
+        let mut client = EvertextClient::connect(&config, "cookie").await.expect("connect");
+        let result = client.run_loop(&account(None), "RESTORE123", RunMode::Daily, &cancel).await;
+        drop(client);
+
+        let err = result.expect_err("session should be cancelled");
+        assert_eq!(err.kind, SessionErrorKind::Cancelled);
+        assert_eq!(server.inputs().await, ["d"]);
+    }
+
+    #[tokio::test]
+    async fn handout_flow_completes() {
+        let script = Script::new()
+            .prompt("Enter Command to use")
+            .prompt("Enter Restore code")
+            .prompt("Press y to spend mana on event stages :")
+            .prompt("Press y to spend mana on event stages :")
+            .output("Press y to perform more commands:");
+
+        let (result, inputs) = run(script, &account(None), RunMode::Handout).await;
+
+        assert!(matches!(result, Ok(SessionOutcome::Completed(_))), "{:?}", result);
+        assert_eq!(inputs, ["ho", "RESTORE123", "ho", "y"]);
+    }
+
+    #[tokio::test]
+    async fn zigza_error_reports_last_prompt() {
+        let script = Script::new()
+            .prompt("Enter Command to use")
+            .prompt("Enter Restore code")
+            .output("Either Zigza error or Incorrect Restore Code Entered");
+
+        let (result, _) = run(script, &account(None), RunMode::Daily).await;
+
+        let err = result.expect_err("zigza should fail the session");
+        assert_eq!(err.kind, SessionErrorKind::Zigza);
+        assert_eq!(err.context.account, "TestAcc");
+        assert_eq!(err.context.last_prompt.as_deref(), Some("Either Zigza error or Incorrect Restore Code Entered"));
+    }
+
+    #[tokio::test]
+    async fn server_events_map_to_error_kinds() {
+        for (event, kind) in [
+            ("idle_timeout", SessionErrorKind::IdleTimeout),
+            ("connection_failed", SessionErrorKind::ConnectionFailed),
+            ("disconnect", SessionErrorKind::ServerDisconnect),
+        ] {
+            let script = Script::new().prompt("Enter Command to use").event(event);
+            let (result, _) = run(script, &account(None), RunMode::Daily).await;
+            assert_eq!(result.expect_err(event).kind, kind);
+        }
+    }
+
+    #[tokio::test]
+    async fn probe_tells_valid_from_refused_cookies() {
+        for (text, expected) in [
+            ("Enter Command to use", CookieCheck::Valid),
+            ("Access to start bot is restricted only for logged in users", CookieCheck::LoginRequired),
+        ] {
+            let server = StandIn::start(Script::new().output(text)).await;
+            let config = ClientConfig {
+                ws_url: server.ws_url.clone(),
+                http_url: server.http_url.clone(),
+                ..ClientConfig::default()
+            };
+            assert_eq!(EvertextClient::probe(&config, "cookie").await, (expected, None));
+            // Nothing may be typed into the game, least of all a restore code
+            assert!(server.inputs().await.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn rotated_session_cookie_is_picked_up() {
+        let server = StandIn::start(Script::new().set_cookie("fresh").output("Enter Command to use")).await;
+        let config = ClientConfig {
+            ws_url: server.ws_url.clone(),
+            http_url: server.http_url.clone(),
+            transcript_dir: String::new(),
+            ..ClientConfig::default()
+        };
+        let refresh = do_http_refresh(&config, "stale").await.expect("refresh");
+        assert_eq!(refresh.rotated.as_deref(), Some("fresh"));
+        // The server handing back the same value is not a rotation
+        assert_eq!(do_http_refresh(&config, "fresh").await.expect("refresh").rotated, None);
+
+        let client = EvertextClient::connect(&config, "stale").await.expect("connect");
+        assert_eq!(client.rotated_cookie(), Some("fresh"));
+        drop(client);
+        server.inputs().await;
+    }
+
+    #[tokio::test]
+    async fn login_and_server_full_are_detected() {
+        for (text, kind) in [
+            ("Access to start bot is restricted only for logged in users", SessionErrorKind::LoginRequired),
+            ("Server reached maximum limit of restore accounts", SessionErrorKind::ServerFull),
+            ("Invalid Command. Exiting Now", SessionErrorKind::InvalidCommand),
+        ] {
+            let script = Script::new().output(text);
+            let (result, _) = run(script, &account(None), RunMode::Daily).await;
+            assert_eq!(result.expect_err(text).kind, kind);
+        }
     }
 }
@@ -0,0 +1,194 @@
+//! Offline stand-in for the EverText server, used by the protocol tests. Enable the
+//! `test-util` feature to use it from integration tests or other crates.
+//!
+//! Speaks just enough Engine.IO/Socket.IO over WebSocket for `EvertextClient`:
+//! the `0{...}` open packet, the `40` namespace join, and `42[...]` events.
+//! Once the client sends `start`, the server plays a fixed script of
+//! `output` events and records every `input` the client answers with.
+//! Plain HTTP requests (the cookie refresh) get an empty `200 OK`, optionally
+//! with a `Set-Cookie` rotating the session.
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::json;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+
+/// One scripted step of a session.
+#[derive(Debug, Clone)]
+pub enum Step {
+    /// Emit an `output` event with this terminal text.
+    Output(String),
+    /// Wait for the client's next `input` event and record it.
+    Input,
+    /// Emit a bare Socket.IO event such as `idle_timeout`.
+    Event(String),
+}
+
+/// Builder for a server script.
+#[derive(Default)]
+pub struct Script {
+    steps: Vec<Step>,
+    set_cookie: Option<String>,
+}
+
+impl Script {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Show `text`, then wait for one answer.
+    pub fn prompt(mut self, text: &str) -> Self {
+        self.steps.push(Step::Output(text.to_string()));
+        self.steps.push(Step::Input);
+        self
+    }
+
+    /// Show `text` without waiting for an answer.
+    pub fn output(mut self, text: &str) -> Self {
+        self.steps.push(Step::Output(text.to_string()));
+        self
+    }
+
+    pub fn event(mut self, name: &str) -> Self {
+        self.steps.push(Step::Event(name.to_string()));
+        self
+    }
+
+    /// Answer HTTP requests with `Set-Cookie: session=<value>`.
+    pub fn set_cookie(mut self, value: &str) -> Self {
+        self.set_cookie = Some(value.to_string());
+        self
+    }
+}
+
+/// A running stand-in. Serves exactly one WebSocket session.
+pub struct StandIn {
+    pub ws_url: String,
+    pub http_url: String,
+    session: JoinHandle<Vec<String>>,
+}
+
+impl StandIn {
+    pub async fn start(script: Script) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind stand-in");
+        let addr = listener.local_addr().expect("stand-in address");
+
+        let session = tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.expect("accept");
+                if is_websocket_upgrade(&stream).await {
+                    return play(stream, script.steps).await;
+                }
+                answer_http(stream, script.set_cookie.as_deref()).await;
+            }
+        });
+
+        Self {
+            ws_url: format!("ws://{}/socket.io/?EIO=4&transport=websocket", addr),
+            http_url: format!("http://{}/", addr),
+            session,
+        }
+    }
+
+    /// Waits for the session to end and returns every `input` the client sent, in order.
+    pub async fn inputs(self) -> Vec<String> {
+        self.session.await.expect("stand-in session panicked")
+    }
+}
+
+async fn is_websocket_upgrade(stream: &TcpStream) -> bool {
+    let mut buf = [0u8; 2048];
+    loop {
+        let n = stream.peek(&mut buf).await.unwrap_or(0);
+        let head = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+        if head.contains("\r\n\r\n") || n == 0 || n == buf.len() {
+            return head.contains("upgrade: websocket");
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    }
+}
+
+async fn answer_http(mut stream: TcpStream, set_cookie: Option<&str>) {
+    let mut buf = [0u8; 2048];
+    let _ = stream.read(&mut buf).await;
+    let cookie = set_cookie.map(|v| format!("Set-Cookie: session={}; Path=/; HttpOnly\r\n", v)).unwrap_or_default();
+    let response = format!("HTTP/1.1 200 OK\r\n{}Content-Length: 0\r\nConnection: close\r\n\r\n", cookie);
+    let _ = stream.write_all(response.as_bytes()).await;
+}
+
+async fn play(stream: TcpStream, steps: Vec<Step>) -> Vec<String> {
+    let mut ws = tokio_tungstenite::accept_async(stream).await.expect("websocket handshake");
+    let mut inputs = Vec::new();
+
+    let open = json!({"sid": "stand-in", "upgrades": [], "pingInterval": 25000, "pingTimeout": 20000});
+    ws.send(Message::Text(format!("0{}", open))).await.expect("send open");
+
+    // Namespace join, then wait for the client to ask for a fresh session
+    while let Some(text) = next_text(&mut ws).await {
+        if text == "40" {
+            ws.send(Message::Text(format!("40{}", json!({"sid": "stand-in-ns"})))).await.expect("send join");
+        } else if event_name(&text).as_deref() == Some("start") {
+            break;
+        }
+    }
+
+    for step in steps {
+        match step {
+            Step::Output(text) => {
+                let packet = format!("42{}", json!(["output", {"data": text}]));
+                if ws.send(Message::Text(packet)).await.is_err() {
+                    break;
+                }
+            }
+            Step::Event(name) => {
+                let packet = format!("42{}", json!([name, {}]));
+                if ws.send(Message::Text(packet)).await.is_err() {
+                    break;
+                }
+            }
+            Step::Input => {
+                let mut answered = false;
+                while let Some(text) = next_text(&mut ws).await {
+                    if let Some(input) = input_value(&text) {
+                        inputs.push(input);
+                        answered = true;
+                        break;
+                    }
+                }
+                if !answered {
+                    break;
+                }
+            }
+        }
+    }
+
+    // Let the client finish and hang up on its own
+    while next_text(&mut ws).await.is_some() {}
+    inputs
+}
+
+async fn next_text(ws: &mut tokio_tungstenite::WebSocketStream<TcpStream>) -> Option<String> {
+    while let Some(msg) = ws.next().await {
+        match msg {
+            Ok(Message::Text(text)) => return Some(text),
+            Ok(Message::Close(_)) | Err(_) => return None,
+            Ok(_) => continue,
+        }
+    }
+    None
+}
+
+fn event_name(text: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(text.strip_prefix("42")?).ok()?;
+    value.get(0)?.as_str().map(str::to_string)
+}
+
+fn input_value(text: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(text.strip_prefix("42")?).ok()?;
+    if value.get(0)?.as_str()? != "input" {
+        return None;
+    }
+    value.get(1)?["input"].as_str().map(str::to_string)
+}
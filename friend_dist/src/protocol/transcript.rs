@@ -0,0 +1,121 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+
+/// Full terminal log of one session, written to disk as it happens.
+///
+/// Layout: `<dir>/<account>/<started>-<mode>-<account>.log`, with `-2`, `-3`, ... appended
+/// when a run of the same account started in the same millisecond. Every line is prefixed with a
+/// UTC timestamp and a marker: `<<` game output, `>>` what we typed, `--` socket events.
+/// The restore code is never written; it shows up as `<restore code>`.
+pub struct Transcript {
+    path: PathBuf,
+    file: Option<File>,
+    secret: Option<String>,
+}
+
+impl Transcript {
+    /// Creates the run's file. Failing to create it only disables the transcript.
+    pub fn create(dir: &str, account: &str, mode: &str, started: DateTime<Utc>) -> Self {
+        let account = safe_name(account);
+        let folder = Path::new(dir).join(&account);
+        let stem = format!("{}-{}-{}", started.format("%Y%m%dT%H%M%S%.3fZ"), mode, account);
+        let mut path = folder.join(format!("{}.log", stem));
+
+        // Never truncate an earlier run's transcript
+        let mut file = fs::create_dir_all(&folder).and_then(|_| OpenOptions::new().write(true).create_new(true).open(&path));
+        for n in 2..100 {
+            match &file {
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    path = folder.join(format!("{}-{}.log", stem, n));
+                    file = OpenOptions::new().write(true).create_new(true).open(&path);
+                },
+                _ => break,
+            }
+        }
+        match file {
+            Ok(file) => Self { path, file: Some(file), secret: None },
+            Err(e) => {
+                println!("[WARN] Could not create transcript {}: {}", path.display(), e);
+                Self { path, file: None, secret: None }
+            }
+        }
+    }
+
+    /// A transcript that records nothing (used when no run was started).
+    pub fn disabled() -> Self {
+        Self { path: PathBuf::new(), file: None, secret: None }
+    }
+
+    /// Text to replace with `<restore code>` wherever it appears.
+    pub fn mask(&mut self, secret: &str) {
+        self.secret = (!secret.is_empty()).then(|| secret.to_string());
+    }
+
+    /// Where the transcript was written, if it was.
+    pub fn path(&self) -> Option<String> {
+        self.file.as_ref().map(|_| self.path.to_string_lossy().into_owned())
+    }
+
+    pub fn output(&mut self, text: &str) {
+        for line in text.lines() {
+            self.write("<<", line);
+        }
+    }
+
+    pub fn input(&mut self, text: &str) {
+        self.write(">>", text);
+    }
+
+    pub fn event(&mut self, text: &str) {
+        self.write("--", text);
+    }
+
+    fn write(&mut self, marker: &str, line: &str) {
+        let Some(file) = self.file.as_mut() else { return };
+        let stamp = Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ");
+        let line = match &self.secret {
+            Some(secret) => line.replace(secret.as_str(), "<restore code>"),
+            None => line.to_string(),
+        };
+        if let Err(e) = writeln!(file, "[{}] {} {}", stamp, marker, line) {
+            println!("[WARN] Transcript write failed for {}: {}", self.path.display(), e);
+            self.file = None;
+        }
+    }
+}
+
+/// Account names are user input; keep them from escaping the transcript directory.
+fn safe_name(name: &str) -> String {
+    let cleaned: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    if cleaned.is_empty() { "_".to_string() } else { cleaned }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_started_together_get_their_own_files() {
+        let dir = std::env::temp_dir().join(format!("evertext-transcripts-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let dir = dir.display().to_string();
+        let started = Utc::now();
+
+        let mut first = Transcript::create(&dir, "Nyx", "daily", started);
+        first.output("first run");
+        let mut second = Transcript::create(&dir, "Nyx", "daily", started);
+        second.output("second run");
+
+        let (a, b) = (first.path().unwrap(), second.path().unwrap());
+        assert_ne!(a, b);
+        assert!(a.contains("-daily-Nyx"), "{}", a);
+        assert!(fs::read_to_string(&a).unwrap().contains("first run"));
+        assert!(fs::read_to_string(&b).unwrap().contains("second run"));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
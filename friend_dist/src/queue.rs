@@ -0,0 +1,326 @@
+//! What the bot's queue managers work through: which accounts are eligible now, which are
+//! backing off, and the live snapshot `/queue_status` shows. Nothing here talks to Discord.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::db::{Account, Database, DbData};
+use crate::pool::{fair_order, Lease, WorkerPool};
+use crate::protocol::config::ClientConfig;
+use crate::protocol::socket::{RunMode, SessionProgress};
+
+/// Run length assumed for the ETA until a run has completed.
+const DEFAULT_RUN_SECS: i64 = 120;
+
+/// Which accounts a queue manager works through.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueueKind {
+    /// Daily run of every unfinished account, optionally limited to one user.
+    Daily(Option<String>),
+    /// One handout run per handout-enabled account.
+    Handout,
+}
+
+impl QueueKind {
+    /// Identifies the manager in the worker pool; one manager per key runs at a time.
+    pub fn key(&self) -> String {
+        match self {
+            QueueKind::Daily(Some(uid)) => format!("daily:{}", uid),
+            QueueKind::Daily(None) => "daily:all".to_string(),
+            QueueKind::Handout => "handout".to_string(),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            QueueKind::Daily(_) => "Queue Manager",
+            QueueKind::Handout => "Handout Manager",
+        }
+    }
+
+    pub fn mode(&self) -> RunMode {
+        match self {
+            QueueKind::Daily(_) => RunMode::Daily,
+            QueueKind::Handout => RunMode::Handout,
+        }
+    }
+
+    /// Accounts this manager may pick up right now, in fair order. `attempts` counts the
+    /// sessions the manager has started per account.
+    pub fn candidates(&self, db: &Database, attempts: &HashMap<String, u32>, now: DateTime<Utc>) -> Vec<Account> {
+        let accs: Vec<Account> = match self {
+            QueueKind::Daily(_) => self.unfinished(db)
+                .filter(|a| a.retry_at().is_none_or(|t| t <= now))
+                .cloned()
+                .collect(),
+            // Handout runs each account once per invocation
+            QueueKind::Handout => db.get_handout_accounts().into_iter()
+                .filter(|a| !attempts.contains_key(&a.name))
+                .collect(),
+        };
+        fair_order(accs)
+    }
+
+    /// Earliest `nextAttemptAt` among accounts still backing off after `now`.
+    pub fn next_retry(&self, db: &Database, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.delayed(db, now).into_iter().map(|(_, at)| at).min()
+    }
+
+    /// Accounts backing off after `now`, with the time each becomes eligible again.
+    pub fn delayed(&self, db: &Database, now: DateTime<Utc>) -> Vec<(String, DateTime<Utc>)> {
+        match self {
+            QueueKind::Daily(_) => self.unfinished(db)
+                .filter_map(|a| a.retry_at().filter(|t| *t > now).map(|t| (a.name.clone(), t)))
+                .collect(),
+            QueueKind::Handout => Vec::new(),
+        }
+    }
+
+    /// Daily accounts that still need a successful run (pending or retrying).
+    pub fn unfinished<'a>(&'a self, db: &'a Database) -> impl Iterator<Item = &'a Account> + 'a {
+        let user_id_filter = match self {
+            QueueKind::Daily(filter) => filter.clone(),
+            QueueKind::Handout => None,
+        };
+        db.data.accounts.iter()
+            .filter(|a| a.needs_daily_run())
+            .filter(move |a| user_id_filter.is_none() || a.user_id == user_id_filter)
+    }
+}
+
+/// Everything a worker needs for one session.
+pub struct WorkerJob {
+    pub acc: Account,
+    pub mode: RunMode,
+    pub cookie_label: String,
+    pub cookie: String,
+    pub config: ClientConfig,
+    /// Sessions this manager already started for the account.
+    pub retries: u32,
+    pub lease: Lease,
+}
+
+/// What a finished worker tells its queue manager.
+#[derive(Debug, PartialEq)]
+pub enum WorkerSignal {
+    Continue,
+    StopQueue,
+    /// The session never got past login; the account gets another go with the next cookie.
+    Requeue(String),
+}
+
+/// Time left until `at`, capped at `max_secs`.
+pub fn until(at: Option<DateTime<Utc>>, max_secs: u64) -> std::time::Duration {
+    let max = std::time::Duration::from_secs(max_secs);
+    at.and_then(|t| (t - Utc::now()).to_std().ok()).map_or(max, |d| d.min(max))
+}
+
+/// Where a queue stands at one moment, for the progress embed and `/queue_status`.
+#[derive(Debug, Clone)]
+pub struct QueueSnapshot {
+    /// Live sessions of every manager, oldest first.
+    pub sessions: Vec<(String, SessionProgress)>,
+    /// Eligible accounts not yet running.
+    pub remaining: usize,
+    /// Accounts backing off, with the time each becomes eligible again.
+    pub delayed: Vec<(String, DateTime<Utc>)>,
+    pub concurrency: usize,
+    /// When everything should be done: the remaining and running accounts in waves of
+    /// `concurrency` at the average completed-run length, and no earlier than one run
+    /// after the last delayed retry.
+    pub eta: DateTime<Utc>,
+}
+
+impl QueueSnapshot {
+    pub fn collect(db: &Database, pool: &WorkerPool, kind: &QueueKind, attempts: &HashMap<String, u32>, now: DateTime<Utc>) -> Self {
+        let sessions = pool.sessions();
+        let remaining = kind.candidates(db, attempts, now).into_iter()
+            .filter(|a| !sessions.iter().any(|(name, _)| *name == a.name))
+            .count();
+        let delayed = kind.delayed(db, now);
+        let concurrency = db.concurrency();
+
+        let avg = average_run_secs(&db.data);
+        let waves = (remaining + sessions.len()).div_ceil(concurrency) as i64;
+        let mut eta = now + chrono::Duration::seconds(avg * waves);
+        if let Some(last_retry) = delayed.iter().map(|(_, t)| *t).max() {
+            eta = eta.max(last_retry + chrono::Duration::seconds(avg));
+        }
+        Self { sessions, remaining, delayed, concurrency, eta }
+    }
+
+    /// True while anything is running, waiting or backing off.
+    pub fn busy(&self) -> bool {
+        self.remaining > 0 || !self.sessions.is_empty() || !self.delayed.is_empty()
+    }
+}
+
+/// Average length of the last 20 completed runs, in seconds.
+fn average_run_secs(data: &DbData) -> i64 {
+    let recent: Vec<i64> = data.history.iter().rev()
+        .filter(|r| r.outcome == "completed")
+        .filter_map(|r| {
+            let s = DateTime::parse_from_rfc3339(&r.started_at).ok()?;
+            let e = DateTime::parse_from_rfc3339(&r.ended_at).ok()?;
+            Some((e - s).num_seconds())
+        })
+        .take(20)
+        .collect();
+    if recent.is_empty() { DEFAULT_RUN_SECS } else { recent.iter().sum::<i64>() / recent.len() as i64 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::LoadMode;
+    use crate::storage::json::JsonStorage;
+    use chrono::Duration;
+    use std::sync::Arc;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-01-01T12:00:00Z").unwrap().with_timezone(&Utc)
+    }
+
+    /// Opens a throwaway JSON database holding `data`.
+    fn open(name: &str, data: serde_json::Value) -> Database {
+        let dir = std::env::temp_dir().join(format!("evertext-queue-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("db.json");
+        std::fs::write(&path, data.to_string()).unwrap();
+        let db = Database::open(Box::new(JsonStorage::new(path.display().to_string())), LoadMode::CookieOptional).unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+        db
+    }
+
+    fn account(name: &str, user: &str, status: &str) -> serde_json::Value {
+        serde_json::json!({"name": name, "code": "x", "pingEnabled": false, "status": status, "userId": user})
+    }
+
+    fn names(accounts: &[Account]) -> Vec<&str> {
+        accounts.iter().map(|a| a.name.as_str()).collect()
+    }
+
+    #[test]
+    fn daily_candidates_wait_out_the_backoff() {
+        let retry_at = now() + Duration::minutes(10);
+        let mut backing_off = account("B", "1", "error: ZIGZA_DETECTED Retrying (1/3)");
+        backing_off["nextAttemptAt"] = retry_at.to_rfc3339().into();
+        let db = open("backoff", serde_json::json!({
+            "accounts": [account("A", "1", "pending"), backing_off, account("C", "1", "done")],
+            "settings": {}
+        }));
+        let kind = QueueKind::Daily(None);
+        let attempts = HashMap::new();
+
+        assert_eq!(names(&kind.candidates(&db, &attempts, now())), ["A"]);
+        assert_eq!(kind.delayed(&db, now()), [("B".to_string(), retry_at)]);
+        assert_eq!(kind.next_retry(&db, now()), Some(retry_at));
+
+        let later = retry_at + Duration::seconds(1);
+        assert_eq!(names(&kind.candidates(&db, &attempts, later)), ["A", "B"], "due retries come after pending accounts");
+        assert!(kind.delayed(&db, later).is_empty());
+        assert_eq!(kind.next_retry(&db, later), None);
+    }
+
+    #[test]
+    fn daily_queue_for_one_user_ignores_the_others() {
+        let mut other = account("C", "2", "error: SERVER_FULL Retrying (1/3)");
+        other["nextAttemptAt"] = (now() + Duration::minutes(5)).to_rfc3339().into();
+        let db = open("user", serde_json::json!({
+            "accounts": [account("A", "1", "pending"), account("B", "2", "pending"), other],
+            "settings": {}
+        }));
+        let kind = QueueKind::Daily(Some("1".to_string()));
+
+        assert_eq!(names(&kind.candidates(&db, &HashMap::new(), now())), ["A"]);
+        assert!(kind.delayed(&db, now()).is_empty());
+        assert_eq!(kind.key(), "daily:1");
+        assert_eq!(QueueKind::Daily(None).key(), "daily:all");
+    }
+
+    #[test]
+    fn handout_runs_each_account_once() {
+        let enabled = |name: &str, status: &str| {
+            let mut acc = account(name, "1", status);
+            acc["handoutEnabled"] = true.into();
+            acc
+        };
+        let db = open("handout", serde_json::json!({
+            "accounts": [enabled("A", "pending"), enabled("B", "done"), account("C", "1", "pending")],
+            "settings": {}
+        }));
+        let kind = QueueKind::Handout;
+
+        assert_eq!(names(&kind.candidates(&db, &HashMap::new(), now())), ["A", "B"]);
+        let attempts = HashMap::from([("A".to_string(), 1)]);
+        assert_eq!(names(&kind.candidates(&db, &attempts, now())), ["B"]);
+        assert!(kind.delayed(&db, now()).is_empty(), "handouts never back off");
+        assert_eq!(kind.mode(), RunMode::Handout);
+    }
+
+    fn run(name: &str, started: DateTime<Utc>, secs: i64, outcome: &str) -> serde_json::Value {
+        serde_json::json!({"account": name, "startedAt": started.to_rfc3339(), "endedAt": (started + Duration::seconds(secs)).to_rfc3339(),
+                           "mode": "daily", "outcome": outcome, "retries": 0, "finalPrompt": null})
+    }
+
+    #[test]
+    fn snapshot_counts_running_remaining_and_delayed() {
+        let retry_at = now() + Duration::minutes(20);
+        let mut backing_off = account("E", "2", "error: ZIGZA_DETECTED Retrying (1/3)");
+        backing_off["nextAttemptAt"] = retry_at.to_rfc3339().into();
+        let db = open("snapshot", serde_json::json!({
+            "accounts": [account("A", "1", "pending"), account("B", "1", "pending"), account("C", "2", "pending"),
+                         account("D", "2", "pending"), backing_off, account("F", "2", "done")],
+            "settings": {"maxConcurrentSessions": 2},
+            "history": [run("A", now() - Duration::days(1), 100, "completed"), run("B", now() - Duration::days(1), 200, "completed"),
+                        run("C", now() - Duration::days(1), 5000, "ZIGZA_DETECTED")]
+        }));
+        let pool = Arc::new(WorkerPool::default());
+        let _lease = pool.try_claim("A", RunMode::Daily, 2).unwrap();
+        let kind = QueueKind::Daily(None);
+
+        let status = QueueSnapshot::collect(&db, &pool, &kind, &HashMap::new(), now());
+        assert_eq!(status.sessions.len(), 1);
+        assert_eq!(status.remaining, 3, "running accounts aren't remaining");
+        assert_eq!(status.delayed, [("E".to_string(), retry_at)]);
+        assert_eq!(status.concurrency, 2);
+        assert!(status.busy());
+        // Failed runs don't count towards the 150s average, and the delayed retry outlasts two waves
+        assert_eq!(status.eta, retry_at + Duration::seconds(150));
+
+        let due = retry_at + Duration::seconds(1);
+        let status = QueueSnapshot::collect(&db, &pool, &kind, &HashMap::new(), due);
+        assert_eq!(status.remaining, 4);
+        assert_eq!(status.eta, due + Duration::seconds(150 * 3), "four remaining and one running in waves of two");
+    }
+
+    #[test]
+    fn snapshot_defaults_without_history_or_settings() {
+        let db = open("snapshot-defaults", serde_json::json!({
+            "accounts": [account("A", "1", "pending"), account("B", "1", "done")],
+            "settings": {"maxConcurrentSessions": 0}
+        }));
+        let pool = Arc::new(WorkerPool::default());
+        let kind = QueueKind::Daily(None);
+
+        let status = QueueSnapshot::collect(&db, &pool, &kind, &HashMap::new(), now());
+        assert_eq!(status.concurrency, 1, "a zero limit still runs one session");
+        assert_eq!(status.eta, now() + Duration::seconds(DEFAULT_RUN_SECS));
+
+        let idle = QueueSnapshot::collect(&db, &pool, &kind, &HashMap::from([("A".to_string(), 1)]), now());
+        assert_eq!(idle.remaining, 1, "daily attempts don't hide an unfinished account");
+        let done = QueueSnapshot::collect(&db, &pool, &QueueKind::Handout, &HashMap::new(), now());
+        assert!(!done.busy(), "nothing handout-enabled");
+    }
+
+    #[test]
+    fn until_is_capped_and_never_negative() {
+        let max = std::time::Duration::from_secs(15);
+        assert_eq!(until(None, 15), max);
+        assert_eq!(until(Some(Utc::now() - Duration::minutes(1)), 15), max, "a time already past falls back to the cap");
+        assert_eq!(until(Some(Utc::now() + Duration::hours(1)), 15), max);
+        assert!(until(Some(Utc::now() + Duration::seconds(5)), 15) <= std::time::Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,158 @@
+use std::time::Duration;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::socket::SessionErrorKind;
+
+/// Failure classes that share a retry budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryClass {
+    /// "Invalid Command" - the game wants a fresh session.
+    InvalidCommand,
+    /// Zigza error / wrong restore code.
+    Zigza,
+    /// The server hit its restore-account limit.
+    ServerFull,
+    /// We could not connect, or the server ended the session (idle, disconnect, ...).
+    Connection,
+    /// The session broke on our side of the wire (timeouts, closed socket, ...).
+    Transport,
+}
+
+impl RetryClass {
+    /// Class of a session failure. `None` means the connection never came up.
+    /// LoginRequired and Cancelled have no class: they stop instead of retrying.
+    pub fn of(kind: Option<SessionErrorKind>) -> Option<RetryClass> {
+        Some(match kind {
+            None => RetryClass::Connection,
+            Some(SessionErrorKind::InvalidCommand) => RetryClass::InvalidCommand,
+            Some(SessionErrorKind::Zigza) => RetryClass::Zigza,
+            Some(SessionErrorKind::ServerFull) => RetryClass::ServerFull,
+            Some(SessionErrorKind::LoginRequired | SessionErrorKind::Cancelled) => return None,
+            Some(SessionErrorKind::IdleTimeout | SessionErrorKind::ConnectionFailed | SessionErrorKind::ServerDisconnect) => RetryClass::Connection,
+            Some(SessionErrorKind::HeartbeatTimeout | SessionErrorKind::ActivityTimeout | SessionErrorKind::PingFailed | SessionErrorKind::SocketClosed | SessionErrorKind::Transport) => RetryClass::Transport,
+        })
+    }
+
+    /// Key used for the per-account attempt counters.
+    pub fn key(&self) -> &'static str {
+        match self {
+            RetryClass::InvalidCommand => "invalidCommand",
+            RetryClass::Zigza => "zigza",
+            RetryClass::ServerFull => "serverFull",
+            RetryClass::Connection => "connection",
+            RetryClass::Transport => "transport",
+        }
+    }
+}
+
+/// Backoff for one failure class: `baseDelaySecs * multiplier^(attempt-1)`,
+/// randomly spread by +/- `jitter` (a fraction, 0.2 = 20%).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Backoff {
+    #[serde(rename = "baseDelaySecs")]
+    pub base_delay_secs: u64,
+    pub multiplier: f64,
+    pub jitter: f64,
+    /// Failures allowed per game day before the account is given up on.
+    #[serde(rename = "maxAttempts")]
+    pub max_attempts: u32,
+}
+
+impl Backoff {
+    const fn new(base_delay_secs: u64, multiplier: f64, jitter: f64, max_attempts: u32) -> Self {
+        Self { base_delay_secs, multiplier, jitter, max_attempts }
+    }
+
+    /// Delay before retrying after the `attempt`-th failure (1-based).
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(32) as i32;
+        let secs = self.base_delay_secs as f64 * self.multiplier.max(1.0).powi(exponent);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let spread = if jitter > 0.0 { rand::thread_rng().gen_range(-jitter..=jitter) } else { 0.0 };
+        Duration::from_secs_f64((secs * (1.0 + spread)).clamp(0.0, 86_400.0))
+    }
+}
+
+/// Retry behaviour per failure class, stored as `Settings.retryPolicy`.
+/// Classes left out of the settings block keep their defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    #[serde(rename = "invalidCommand")]
+    pub invalid_command: Backoff,
+    pub zigza: Backoff,
+    #[serde(rename = "serverFull")]
+    pub server_full: Backoff,
+    pub connection: Backoff,
+    pub transport: Backoff,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            invalid_command: Backoff::new(5, 1.0, 0.0, 5),
+            zigza: Backoff::new(600, 1.5, 0.1, 3),
+            server_full: Backoff::new(300, 1.5, 0.2, 6),
+            connection: Backoff::new(5, 2.0, 0.2, 6),
+            transport: Backoff::new(30, 2.0, 0.2, 3),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn backoff(&self, class: RetryClass) -> &Backoff {
+        match class {
+            RetryClass::InvalidCommand => &self.invalid_command,
+            RetryClass::Zigza => &self.zigza,
+            RetryClass::ServerFull => &self.server_full,
+            RetryClass::Connection => &self.connection,
+            RetryClass::Transport => &self.transport,
+        }
+    }
+}
+
+/// Short human form of a delay, e.g. "45s", "10m", "1h 30m".
+pub fn describe(delay: Duration) -> String {
+    let secs = delay.as_secs();
+    match secs {
+        0..=119 => format!("{}s", secs),
+        120..=3599 => format!("{}m", secs / 60),
+        _ if secs % 3600 / 60 == 0 => format!("{}h", secs / 3600),
+        _ => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_grows_exponentially_within_jitter() {
+        let backoff = Backoff::new(10, 2.0, 0.0, 5);
+        assert_eq!(backoff.delay(1), Duration::from_secs(10));
+        assert_eq!(backoff.delay(3), Duration::from_secs(40));
+
+        let jittered = Backoff::new(100, 1.0, 0.2, 5);
+        for _ in 0..50 {
+            let d = jittered.delay(1).as_secs_f64();
+            assert!((80.0..=120.0).contains(&d), "{}", d);
+        }
+    }
+
+    #[test]
+    fn partial_settings_keep_defaults() {
+        let policy: RetryPolicy = serde_json::from_str(r#"{"zigza": {"baseDelaySecs": 60, "multiplier": 1, "jitter": 0, "maxAttempts": 1}}"#).unwrap();
+        assert_eq!(policy.backoff(RetryClass::Zigza).max_attempts, 1);
+        assert_eq!(policy.backoff(RetryClass::ServerFull).max_attempts, RetryPolicy::default().server_full.max_attempts);
+    }
+
+    #[test]
+    fn login_required_is_not_retried() {
+        assert_eq!(RetryClass::of(Some(SessionErrorKind::LoginRequired)), None);
+        assert_eq!(RetryClass::of(None), Some(RetryClass::Connection));
+        assert_eq!(RetryClass::of(Some(SessionErrorKind::SocketClosed)), Some(RetryClass::Transport));
+        assert_eq!(describe(Duration::from_secs(5400)), "1h 30m");
+    }
+}
@@ -0,0 +1,286 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use cron::Schedule;
+
+use crate::db::{JobSchedule, Settings};
+
+/// Everything the scheduler knows how to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Job {
+    /// Marks every account "pending" again.
+    DailyReset,
+    /// Runs the daily queue for all accounts.
+    DailyRun,
+    /// Runs the handout queue.
+    HandoutRun,
+    /// Hits the EverText site with the session cookie to keep it alive.
+    CookieRefresh,
+    /// Probes every pool cookie and alerts admins about expired ones.
+    CookieCheck,
+    /// Writes a JSON snapshot of the database.
+    Backup,
+}
+
+impl Job {
+    /// In execution order: when several jobs are due at once, the reset runs before the daily run.
+    pub const ALL: [Job; 6] = [Job::DailyReset, Job::DailyRun, Job::HandoutRun, Job::CookieRefresh, Job::CookieCheck, Job::Backup];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Job::DailyReset => "daily_reset",
+            Job::DailyRun => "daily_run",
+            Job::HandoutRun => "handout_run",
+            Job::CookieRefresh => "cookie_refresh",
+            Job::CookieCheck => "cookie_check",
+            Job::Backup => "backup",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Job> {
+        Job::ALL.into_iter().find(|j| j.name() == name)
+    }
+
+    /// The schedule a fresh install gets. Reset and daily run keep the old
+    /// 00:00 UTC trigger and the cookie check runs a few hours ahead of it;
+    /// the rest are opt-in.
+    fn default_schedule(&self) -> JobSchedule {
+        let (cron, enabled) = match self {
+            Job::DailyReset => ("0 0 * * *", true),
+            Job::DailyRun => ("0 0 * * *", true),
+            Job::HandoutRun => ("0 11 * * *", false),
+            Job::CookieRefresh => ("0 */6 * * *", false),
+            Job::CookieCheck => ("0 6,18 * * *", true),
+            Job::Backup => ("30 0 * * *", false),
+        };
+        JobSchedule {
+            name: self.name().to_string(),
+            cron: cron.to_string(),
+            timezone: "UTC".to_string(),
+            enabled,
+            next_run: None,
+        }
+    }
+}
+
+/// Parses a cron expression. Plain 5-field crontab lines (`min hour dom mon dow`)
+/// are accepted as well as the 6/7-field form with seconds.
+pub fn parse_cron(expr: &str) -> Result<Schedule, String> {
+    let fields = expr.split_whitespace().count();
+    let full = if fields == 5 { format!("0 {}", expr.trim()) } else { expr.trim().to_string() };
+    Schedule::from_str(&full).map_err(|e| format!("invalid cron expression '{}': {}", expr, e))
+}
+
+pub fn parse_timezone(name: &str) -> Result<Tz, String> {
+    Tz::from_str(name).map_err(|_| format!("unknown timezone '{}'", name))
+}
+
+/// First time after `after` at which the job fires.
+pub fn next_after(job: &JobSchedule, after: DateTime<Utc>) -> Result<DateTime<Utc>, String> {
+    let schedule = parse_cron(&job.cron)?;
+    let tz = parse_timezone(&job.timezone)?;
+    schedule.after(&after.with_timezone(&tz))
+        .next()
+        .map(|t| t.with_timezone(&Utc))
+        .ok_or_else(|| format!("'{}' never fires", job.cron))
+}
+
+/// Most recent time at or before `at` at which the job fired (or should have).
+pub fn last_at_or_before(job: &JobSchedule, at: DateTime<Utc>) -> Result<DateTime<Utc>, String> {
+    let schedule = parse_cron(&job.cron)?;
+    let tz = parse_timezone(&job.timezone)?;
+    // `after` excludes its start, so look back from one second later
+    schedule.after(&(at + chrono::Duration::seconds(1)).with_timezone(&tz))
+        .next_back()
+        .map(|t| t.with_timezone(&Utc))
+        .ok_or_else(|| format!("'{}' never fired", job.cron))
+}
+
+/// The game day `now` belongs to: the date of the latest `daily_reset` occurrence,
+/// in the job's timezone (`%Y-%m-%d`, the format of `Settings.last_reset_date`).
+/// None when the reset job is disabled or missing.
+pub fn current_game_day(settings: &Settings, now: DateTime<Utc>) -> Option<String> {
+    let job = settings.schedules.iter().find(|s| s.name == Job::DailyReset.name() && s.enabled)?;
+    let tz = parse_timezone(&job.timezone).ok()?;
+    let last = last_at_or_before(job, now).ok()?;
+    Some(last.with_timezone(&tz).format("%Y-%m-%d").to_string())
+}
+
+/// A daily reset that should have happened while the bot was down.
+#[derive(Debug, PartialEq)]
+pub struct MissedReset {
+    pub game_day: String,
+    pub previous: Option<String>,
+    /// Whether the daily run job is enabled, i.e. the caller should queue it.
+    pub run_daily: bool,
+}
+
+/// Checks `last_reset_date` against the current game day. If the reset was
+/// missed, records it as done and moves `daily_reset`/`daily_run` past `now`
+/// so `take_due` doesn't fire them a second time. The caller performs the reset.
+pub fn take_missed_reset(settings: &mut Settings, now: DateTime<Utc>) -> Option<MissedReset> {
+    let game_day = current_game_day(settings, now)?;
+    if settings.last_reset_date.as_deref().is_some_and(|last| last >= game_day.as_str()) {
+        return None;
+    }
+
+    let previous = settings.last_reset_date.replace(game_day.clone());
+    let mut run_daily = false;
+    for entry in settings.schedules.iter_mut().filter(|s| s.enabled) {
+        if entry.name == Job::DailyReset.name() || entry.name == Job::DailyRun.name() {
+            run_daily |= entry.name == Job::DailyRun.name();
+            if let Ok(next) = next_after(entry, now) {
+                entry.next_run = Some(next.to_rfc3339());
+            }
+        }
+    }
+    Some(MissedReset { game_day, previous, run_daily })
+}
+
+/// Adds any job missing from `settings.schedules` with its default schedule.
+/// Returns true if something was added.
+pub fn ensure_defaults(settings: &mut Settings) -> bool {
+    let mut changed = false;
+    for job in Job::ALL {
+        if !settings.schedules.iter().any(|s| s.name == job.name()) {
+            settings.schedules.push(job.default_schedule());
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Jobs whose persisted next run is at or before `now`, in `Job::ALL` order.
+/// Each due job fires once, however many runs were missed, and its next run
+/// moves past `now`. Enabled jobs without a next run just get one computed.
+/// Returns the due jobs and whether any schedule changed.
+pub fn take_due(settings: &mut Settings, now: DateTime<Utc>) -> (Vec<Job>, bool) {
+    let mut due = Vec::new();
+    let mut changed = false;
+
+    for job in Job::ALL {
+        let Some(entry) = settings.schedules.iter_mut().find(|s| s.name == job.name()) else { continue };
+        if !entry.enabled {
+            continue;
+        }
+
+        let next_run = entry.next_run.as_deref()
+            .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+            .map(|t| t.with_timezone(&Utc));
+        if next_run.is_some_and(|t| t <= now) {
+            due.push(job);
+        } else if next_run.is_some() {
+            continue;
+        }
+
+        match next_after(entry, now) {
+            Ok(next) => entry.next_run = Some(next.to_rfc3339()),
+            Err(e) => {
+                println!("[ERROR] Scheduler: Job '{}' disabled: {}", entry.name, e);
+                entry.enabled = false;
+                entry.next_run = None;
+            }
+        }
+        changed = true;
+    }
+    (due, changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn settings_with(name: &str, cron: &str, timezone: &str, next_run: Option<DateTime<Utc>>) -> Settings {
+        let mut settings = Settings::default();
+        settings.schedules.push(JobSchedule {
+            name: name.to_string(),
+            cron: cron.to_string(),
+            timezone: timezone.to_string(),
+            enabled: true,
+            next_run: next_run.map(|t| t.to_rfc3339()),
+        });
+        settings
+    }
+
+    #[test]
+    fn five_field_cron_honours_timezone() {
+        let job = settings_with("daily_run", "30 5 * * *", "Asia/Kolkata", None).schedules.remove(0);
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        // 05:30 IST is 00:00 UTC
+        assert_eq!(next_after(&job, now).unwrap(), Utc.with_ymd_and_hms(2024, 3, 2, 0, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn missed_runs_catch_up_once() {
+        let missed = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 3, 4, 9, 0, 0).unwrap();
+        let mut settings = settings_with("daily_reset", "0 0 * * *", "UTC", Some(missed));
+
+        let (due, changed) = take_due(&mut settings, now);
+        assert_eq!(due, [Job::DailyReset]);
+        assert!(changed);
+        assert_eq!(settings.schedules[0].next_run.as_deref(), Some(Utc.with_ymd_and_hms(2024, 3, 5, 0, 0, 0).unwrap().to_rfc3339().as_str()));
+
+        let (due, changed) = take_due(&mut settings, now);
+        assert!(due.is_empty());
+        assert!(!changed);
+    }
+
+    #[test]
+    fn new_and_disabled_jobs_do_not_fire() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let mut settings = settings_with("backup", "0 0 * * *", "UTC", None);
+        assert!(take_due(&mut settings, now).0.is_empty());
+        assert!(settings.schedules[0].next_run.is_some());
+
+        settings.schedules[0].enabled = false;
+        settings.schedules[0].next_run = Some(now.to_rfc3339());
+        assert_eq!(take_due(&mut settings, now), (vec![], false));
+    }
+
+    #[test]
+    fn missed_reset_is_taken_once_per_game_day() {
+        let mut settings = Settings::default();
+        ensure_defaults(&mut settings);
+        settings.last_reset_date = Some("2024-03-03".to_string());
+        let now = Utc.with_ymd_and_hms(2024, 3, 4, 0, 3, 0).unwrap();
+
+        let missed = take_missed_reset(&mut settings, now).expect("reset was missed");
+        assert_eq!(missed, MissedReset {
+            game_day: "2024-03-04".to_string(),
+            previous: Some("2024-03-03".to_string()),
+            run_daily: true,
+        });
+        assert_eq!(settings.last_reset_date.as_deref(), Some("2024-03-04"));
+        // The scheduler must not reset a second time
+        assert!(take_due(&mut settings, now).0.is_empty());
+        assert!(take_missed_reset(&mut settings, now).is_none());
+    }
+
+    #[test]
+    fn game_day_follows_reset_timezone() {
+        let mut settings = settings_with("daily_reset", "0 0 * * *", "Asia/Jakarta", None);
+        // 18:00 UTC is already past midnight in Jakarta (UTC+7)
+        let now = Utc.with_ymd_and_hms(2024, 3, 4, 18, 0, 0).unwrap();
+        assert_eq!(current_game_day(&settings, now).as_deref(), Some("2024-03-05"));
+
+        settings.last_reset_date = Some("2024-03-05".to_string());
+        assert!(take_missed_reset(&mut settings, now).is_none());
+        settings.schedules[0].enabled = false;
+        assert!(current_game_day(&settings, now).is_none());
+    }
+
+    #[test]
+    fn defaults_cover_every_job() {
+        let mut settings = Settings::default();
+        assert!(ensure_defaults(&mut settings));
+        assert!(!ensure_defaults(&mut settings));
+        for job in Job::ALL {
+            assert!(settings.schedules.iter().any(|s| s.name == job.name()));
+        }
+        assert!(parse_cron("not a cron").is_err());
+        assert!(parse_timezone("Mars/Olympus").is_err());
+    }
+}
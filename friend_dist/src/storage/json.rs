@@ -0,0 +1,192 @@
+use std::fs;
+
+use super::{Storage, StoreResult};
+use crate::db::DbData;
+
+/// The original storage: the whole database as one pretty-printed JSON file.
+pub struct JsonStorage {
+    path: String,
+    legacy_fallback: bool,
+}
+
+impl JsonStorage {
+    /// Strict: loading fails if `path` doesn't exist.
+    pub fn new(path: String) -> Self {
+        Self { path, legacy_fallback: false }
+    }
+
+    /// Falls back to other well-known locations, and (with the `embedded-db`
+    /// feature) to the db.json compiled into the binary, when `path` is missing.
+    pub fn with_legacy_fallback(path: String) -> Self {
+        Self { path, legacy_fallback: true }
+    }
+
+    fn load_legacy(&self) -> StoreResult<String> {
+        let path = self.path.clone();
+
+        // --- Diagnostics ---
+        if let Ok(cwd) = std::env::current_dir() {
+            println!("[DEBUG] Current working directory: {:?}", cwd);
+        }
+        for dir in [".", "/app", "/"] {
+            if let Ok(entries) = fs::read_dir(dir) {
+                let files: Vec<_> = entries.filter_map(|e| e.ok().map(|e| e.file_name().into_string().unwrap_or_default())).collect();
+                println!("[DEBUG] Files in '{}': {:?}", dir, files);
+            }
+        }
+        // --- End Diagnostics ---
+
+        let content = match fs::read_to_string(&path) {
+            Ok(c) => {
+                println!("[INFO] Loading database from file: {}", path);
+                c
+            },
+            Err(_e) => {
+                println!("[WARN] Could not find database at {}. Searching fallbacks...", path);
+                // Try several fallback locations
+                let fallbacks = [
+                    "db.json", 
+                    "./db.json", 
+                    "/app/db.json", 
+                    "app/db.json", 
+                    "../db.json"
+                ];
+                let mut found_content = None;
+                
+                for fb in fallbacks {
+                    if let Ok(c) = fs::read_to_string(fb) {
+                        println!("[INFO] Found database at fallback: {}", fb);
+                        found_content = Some(c);
+                        break;
+                    }
+                }
+                
+                match found_content {
+                    Some(c) => {
+                        println!("[INFO] Using database from fallback file.");
+                        c
+                    },
+                    None => Self::restore_embedded()?,
+                }
+            }
+        };
+        Ok(content)
+    }
+
+    #[cfg(feature = "embedded-db")]
+    fn restore_embedded() -> StoreResult<String> {
+        println!("[WARN] No database file found on disk. Using EMBEDDED database fallback.");
+        let embedded = include_str!("../../db.json").to_string();
+        // AUTO-RESTORE: Write the embedded content to disk so we can save later
+        let restore_path = "db.json";
+        if let Err(e) = fs::write(restore_path, &embedded) {
+            println!("[WARN] Failed to restore db.json to disk: {}", e);
+        } else {
+            println!("[INFO] successfully restored db.json from embedded backup to '{}'", restore_path);
+        }
+        Ok(embedded)
+    }
+
+    #[cfg(not(feature = "embedded-db"))]
+    fn restore_embedded() -> StoreResult<String> {
+        Err("No database file found in any fallback location (this build has no embedded db.json)".into())
+    }
+}
+
+impl Storage for JsonStorage {
+    fn load(&mut self) -> StoreResult<DbData> {
+        let content = if self.legacy_fallback {
+            self.load_legacy()?
+        } else {
+            match fs::read_to_string(&self.path) {
+                Ok(c) => {
+                    println!("[INFO] Loading database from file: {}", self.path);
+                    c
+                },
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    let cwd = std::env::current_dir().map(|d| d.display().to_string()).unwrap_or_else(|_| "?".to_string());
+                    return Err(format!(
+                        "database file '{}' not found (working directory: {}). Point DATABASE_PATH at your db.json, or set DATABASE_LEGACY_FALLBACK=true to search the old locations",
+                        self.path, cwd
+                    ).into());
+                },
+                Err(e) => return Err(format!("cannot read database file '{}': {}", self.path, e).into()),
+            }
+        };
+
+        match serde_json::from_str::<DbData>(&content) {
+            Ok(data) => Ok(data),
+            Err(e) => {
+                println!("[ERROR] Failed to parse database JSON: {}", e);
+                // If parsing fails, we might as well return the error, 
+                // but at least we tried every path.
+                Err(e.into())
+            }
+        }
+    }
+
+    fn save_all(&mut self, data: &DbData) -> StoreResult<()> {
+        let content = serde_json::to_string_pretty(data)?;
+        
+        // Legacy mode tries other locations too, to ensure persistence if possible
+        let paths: &[&str] = if self.legacy_fallback { &[self.path.as_str(), "db.json", "/app/db.json"] } else { &[self.path.as_str()] };
+        let mut saved = false;
+
+        for &p in paths {
+            if let Err(e) = write_atomic(p, &content) {
+                println!("[WARN] Failed to save database to {}: {}", p, e);
+            } else {
+                println!("[INFO] Successfully saved database to {}", p);
+                saved = true;
+                // We only need to save to one location successfully
+                break; // Added break here to stop trying once saved
+            }
+        }
+
+        if !saved {
+            println!("[ERROR] Failed to save database to ANY location!");
+            return Err("Failed to save database to any location".into());
+        }
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        format!("json:{}", self.path)
+    }
+}
+
+/// Writes through a temporary file and a rename, so a crash never leaves a half-written database.
+fn write_atomic(path: &str, content: &str) -> std::io::Result<()> {
+    let tmp = format!("{}.tmp", path);
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{Database, LoadMode};
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("evertext-{}-{}.json", name, std::process::id())).display().to_string()
+    }
+
+    #[test]
+    fn strict_load_rejects_missing_file_and_missing_cookie() {
+        let missing = temp_path("missing");
+        let err = JsonStorage::new(missing.clone()).load().expect_err("missing file must fail");
+        assert!(err.to_string().contains(&missing), "{}", err);
+
+        let path = temp_path("no-cookie");
+        fs::write(&path, r#"{"accounts": [], "settings": {"cookies": null}}"#).unwrap();
+        assert!(Database::open(Box::new(JsonStorage::new(path.clone())), LoadMode::Strict).is_err());
+        let db = Database::open(Box::new(JsonStorage::new(path.clone())), LoadMode::Legacy).expect("legacy starts without a cookie");
+        assert!(db.data.settings.session_cookies.is_empty());
+        assert!(Database::open(Box::new(JsonStorage::new(path.clone())), LoadMode::CookieOptional).is_ok());
+
+        fs::write(&path, r#"{"accounts": [], "settings": {"cookies": "abc"}}"#).unwrap();
+        let db = Database::open(Box::new(JsonStorage::new(path.clone())), LoadMode::Strict).expect("cookie present");
+        assert_eq!(db.data.settings.session_cookies[0].label, "default");
+        let _ = fs::remove_file(path);
+    }
+}
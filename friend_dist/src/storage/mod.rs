@@ -0,0 +1,39 @@
+pub mod json;
+pub mod sqlite;
+
+use crate::db::{Account, DbData, RunRecord};
+
+pub type StoreResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Where `Database` keeps its data. `Database` owns the in-memory copy and
+/// tells the backend what changed; backends that can write incrementally
+/// override the per-record methods, the rest fall back to a full rewrite.
+pub trait Storage: Send {
+    fn load(&mut self) -> StoreResult<DbData>;
+
+    fn save_all(&mut self, data: &DbData) -> StoreResult<()>;
+
+    fn save_accounts(&mut self, data: &DbData, _accounts: &[&Account]) -> StoreResult<()> {
+        self.save_all(data)
+    }
+
+    fn delete_account(&mut self, data: &DbData, _name: &str) -> StoreResult<()> {
+        self.save_all(data)
+    }
+
+    fn save_settings(&mut self, data: &DbData) -> StoreResult<()> {
+        self.save_all(data)
+    }
+
+    fn append_history(&mut self, data: &DbData, _record: &RunRecord) -> StoreResult<()> {
+        self.save_all(data)
+    }
+
+    /// Removes history entries that started before `cutoff` (RFC 3339).
+    fn prune_history(&mut self, data: &DbData, _cutoff: &str) -> StoreResult<()> {
+        self.save_all(data)
+    }
+
+    /// Human readable description for log lines, e.g. "json:db.json".
+    fn describe(&self) -> String;
+}
@@ -0,0 +1,193 @@
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::{Storage, StoreResult};
+use crate::db::{Account, DbData, RunRecord, Settings};
+
+/// Embedded SQLite storage. Each account is its own row, so a status update
+/// rewrites one row inside a transaction instead of the whole file.
+///
+/// Rows hold the same JSON the file backend uses, so new `Account`/`Settings`
+/// fields need no schema migration.
+pub struct SqliteStorage {
+    path: String,
+    conn: Connection,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &str) -> StoreResult<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS accounts (
+                 name TEXT PRIMARY KEY NOT NULL,
+                 data TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS settings (
+                 id INTEGER PRIMARY KEY CHECK (id = 0),
+                 data TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS history (
+                 id INTEGER PRIMARY KEY AUTOINCREMENT,
+                 account TEXT NOT NULL,
+                 started_at TEXT NOT NULL,
+                 data TEXT NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS history_account ON history (account, started_at);",
+        )?;
+        println!("[INFO] Opened SQLite database: {}", path);
+        Ok(Self { path: path.to_string(), conn })
+    }
+
+    fn upsert_account(tx: &rusqlite::Transaction, account: &Account) -> StoreResult<()> {
+        tx.execute(
+            "INSERT INTO accounts (name, data) VALUES (?1, ?2)
+             ON CONFLICT(name) DO UPDATE SET data = excluded.data",
+            params![account.name, serde_json::to_string(account)?],
+        )?;
+        Ok(())
+    }
+
+    fn insert_history(tx: &rusqlite::Transaction, record: &RunRecord) -> StoreResult<()> {
+        tx.execute(
+            "INSERT INTO history (account, started_at, data) VALUES (?1, ?2, ?3)",
+            params![record.account, record.started_at, serde_json::to_string(record)?],
+        )?;
+        Ok(())
+    }
+
+    fn upsert_settings(tx: &rusqlite::Transaction, settings: &Settings) -> StoreResult<()> {
+        tx.execute(
+            "INSERT INTO settings (id, data) VALUES (0, ?1)
+             ON CONFLICT(id) DO UPDATE SET data = excluded.data",
+            params![serde_json::to_string(settings)?],
+        )?;
+        Ok(())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn load(&mut self) -> StoreResult<DbData> {
+        // rowid keeps insertion order, which the queue relies on
+        let mut stmt = self.conn.prepare("SELECT data FROM accounts ORDER BY rowid")?;
+        let accounts = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .map(|raw| Ok(serde_json::from_str::<Account>(&raw?)?))
+            .collect::<StoreResult<Vec<Account>>>()?;
+
+        let settings = match self.conn
+            .query_row("SELECT data FROM settings WHERE id = 0", [], |row| row.get::<_, String>(0))
+            .optional()?
+        {
+            Some(raw) => serde_json::from_str(&raw)?,
+            None => Settings::default(),
+        };
+
+        let mut stmt = self.conn.prepare("SELECT data FROM history ORDER BY id")?;
+        let history = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .map(|raw| Ok(serde_json::from_str::<RunRecord>(&raw?)?))
+            .collect::<StoreResult<Vec<RunRecord>>>()?;
+
+        println!("[INFO] Loaded {} accounts from SQLite database: {}", accounts.len(), self.path);
+        Ok(DbData { accounts, settings, history })
+    }
+
+    fn save_all(&mut self, data: &DbData) -> StoreResult<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM accounts", [])?;
+        for account in &data.accounts {
+            Self::upsert_account(&tx, account)?;
+        }
+        Self::upsert_settings(&tx, &data.settings)?;
+        tx.execute("DELETE FROM history", [])?;
+        for record in &data.history {
+            Self::insert_history(&tx, record)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn save_accounts(&mut self, _data: &DbData, accounts: &[&Account]) -> StoreResult<()> {
+        let tx = self.conn.transaction()?;
+        for account in accounts {
+            Self::upsert_account(&tx, account)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn delete_account(&mut self, _data: &DbData, name: &str) -> StoreResult<()> {
+        self.conn.execute("DELETE FROM accounts WHERE name = ?1", params![name])?;
+        Ok(())
+    }
+
+    fn save_settings(&mut self, data: &DbData) -> StoreResult<()> {
+        let tx = self.conn.transaction()?;
+        Self::upsert_settings(&tx, &data.settings)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn append_history(&mut self, _data: &DbData, record: &RunRecord) -> StoreResult<()> {
+        let tx = self.conn.transaction()?;
+        Self::insert_history(&tx, record)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn prune_history(&mut self, _data: &DbData, cutoff: &str) -> StoreResult<()> {
+        self.conn.execute("DELETE FROM history WHERE started_at < ?1", params![cutoff])?;
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        format!("sqlite:{}", self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(account: &str, started_at: &str) -> RunRecord {
+        RunRecord {
+            account: account.to_string(),
+            started_at: started_at.to_string(),
+            ended_at: started_at.to_string(),
+            mode: "daily".to_string(),
+            outcome: "completed".to_string(),
+            retries: 0,
+            final_prompt: None,
+            transcript: None,
+        }
+    }
+
+    #[test]
+    fn incremental_writes_survive_reopen() {
+        let path = std::env::temp_dir().join(format!("evertext-sqlite-{}.db", std::process::id())).display().to_string();
+        let _ = std::fs::remove_file(&path);
+        let mut data = DbData { accounts: vec![Account::new("A", "a".to_string())], settings: Settings::default(), history: vec![] };
+        let mut store = SqliteStorage::open(&path).unwrap();
+        store.save_all(&data).unwrap();
+
+        data.accounts.push(Account::new("B", "b".to_string()));
+        data.accounts[0].status = "done".to_string();
+        store.save_accounts(&data, &[&data.accounts[1], &data.accounts[0]]).unwrap();
+        store.delete_account(&data, "missing").unwrap();
+        store.append_history(&data, &record("A", "2024-01-01T00:00:00Z")).unwrap();
+        store.append_history(&data, &record("B", "2024-02-01T00:00:00Z")).unwrap();
+        store.prune_history(&data, "2024-01-15T00:00:00Z").unwrap();
+        data.settings.admins.push("1".to_string());
+        store.save_settings(&data).unwrap();
+        drop(store);
+
+        let loaded = SqliteStorage::open(&path).unwrap().load().unwrap();
+        let names: Vec<_> = loaded.accounts.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["A", "B"], "existing rows keep their position");
+        assert_eq!(loaded.accounts[0].status, "done");
+        assert_eq!(loaded.settings.admins, ["1"]);
+        assert_eq!(loaded.history.len(), 1);
+        assert_eq!(loaded.history[0].account, "B");
+        let _ = std::fs::remove_file(path);
+    }
+}